@@ -0,0 +1,127 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Location of ffmpeg/ffprobe and limits applied to their runs, read from the environment
+#[derive(Debug, Clone)]
+pub struct FfmpegConfig {
+    /// ffmpeg executable, looked up on PATH unless absolute (`FFMPEG_PATH`)
+    pub ffmpeg_path: String,
+    /// ffprobe executable (`FFPROBE_PATH`)
+    pub ffprobe_path: String,
+    /// Time every run gets regardless of input length (`FFMPEG_TIMEOUT_SECS`)
+    pub timeout_base: Duration,
+    /// Extra wall-clock seconds per second of input (`FFMPEG_TIMEOUT_FACTOR`)
+    pub timeout_factor: f64,
+    /// Upper bound for a single run (`FFMPEG_MAX_TIMEOUT_SECS`)
+    pub timeout_max: Duration,
+}
+
+impl Default for FfmpegConfig {
+    fn default() -> Self {
+        Self {
+            ffmpeg_path: "ffmpeg".to_string(),
+            ffprobe_path: "ffprobe".to_string(),
+            timeout_base: Duration::from_secs(60),
+            timeout_factor: 10.0,
+            timeout_max: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl FfmpegConfig {
+    /// Read the configuration, keeping the default for unset or invalid variables
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            ffmpeg_path: env_path("FFMPEG_PATH").unwrap_or(defaults.ffmpeg_path),
+            ffprobe_path: env_path("FFPROBE_PATH").unwrap_or(defaults.ffprobe_path),
+            timeout_base: env_secs("FFMPEG_TIMEOUT_SECS").unwrap_or(defaults.timeout_base),
+            timeout_factor: env_number("FFMPEG_TIMEOUT_FACTOR").unwrap_or(defaults.timeout_factor),
+            timeout_max: env_secs("FFMPEG_MAX_TIMEOUT_SECS").unwrap_or(defaults.timeout_max),
+        }
+    }
+
+    /// Wall-clock limit for one run over `duration` seconds of input
+    pub fn timeout_for(&self, duration: Option<f64>) -> Duration {
+        let scaled = duration
+            .filter(|d| d.is_finite() && *d > 0.0)
+            .map(|d| Duration::from_secs_f64(d * self.timeout_factor))
+            .unwrap_or_default();
+        (self.timeout_base + scaled).min(self.timeout_max)
+    }
+}
+
+/// Process-wide ffmpeg configuration, read on first use
+pub fn ffmpeg() -> &'static FfmpegConfig {
+    static CONFIG: OnceLock<FfmpegConfig> = OnceLock::new();
+    CONFIG.get_or_init(FfmpegConfig::from_env)
+}
+
+/// Sizes of the conversion worker pools, read from the environment
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    /// Concurrent image conversions (`IMAGE_WORKERS`)
+    pub image_workers: usize,
+    /// Image requests allowed to wait for a worker (`IMAGE_QUEUE_SIZE`)
+    pub image_queue: usize,
+    /// Concurrent ffmpeg jobs (`VIDEO_WORKERS`)
+    pub video_workers: usize,
+    /// Video and audio requests allowed to wait for a worker (`VIDEO_QUEUE_SIZE`)
+    pub video_queue: usize,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        let cpus = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
+        Self {
+            image_workers: cpus,
+            image_queue: 32,
+            // ffmpeg is multi-threaded itself, so a couple of jobs already keep every core busy
+            video_workers: (cpus / 4).clamp(1, 4),
+            video_queue: 8,
+        }
+    }
+}
+
+impl WorkerConfig {
+    /// Read the configuration, keeping the default for unset or invalid variables
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            image_workers: env_count("IMAGE_WORKERS", 1).unwrap_or(defaults.image_workers),
+            image_queue: env_count("IMAGE_QUEUE_SIZE", 0).unwrap_or(defaults.image_queue),
+            video_workers: env_count("VIDEO_WORKERS", 1).unwrap_or(defaults.video_workers),
+            video_queue: env_count("VIDEO_QUEUE_SIZE", 0).unwrap_or(defaults.video_queue),
+        }
+    }
+}
+
+fn env_path(name: &str) -> Option<String> {
+    std::env::var(name).ok().map(|value| value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+fn env_count(name: &str, min: usize) -> Option<usize> {
+    let value = std::env::var(name).ok()?;
+    match value.trim().parse::<usize>() {
+        Ok(count) if count >= min => Some(count),
+        _ => {
+            tracing::warn!("Ignoring invalid {}='{}', expected a whole number of at least {}", name, value, min);
+            None
+        }
+    }
+}
+
+fn env_number(name: &str) -> Option<f64> {
+    let value = std::env::var(name).ok()?;
+    match value.trim().parse::<f64>() {
+        Ok(number) if number.is_finite() && number >= 0.0 => Some(number),
+        _ => {
+            tracing::warn!("Ignoring invalid {}='{}', expected a non-negative number", name, value);
+            None
+        }
+    }
+}
+
+fn env_secs(name: &str) -> Option<Duration> {
+    env_number(name).map(Duration::from_secs_f64)
+}
@@ -1,18 +1,105 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
     response::{IntoResponse, Json},
 };
+use serde::Serialize;
 use serde_json::json;
 use thiserror::Error;
 
+/// Values accepted by a request parameter, reported back when it is rejected
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Allowed {
+    /// One of a fixed set of values
+    OneOf(Vec<String>),
+    /// A number within an inclusive range
+    Range { min: String, max: String },
+    /// A value in the described format
+    Format(String),
+}
+
+/// A request parameter that failed validation
+#[derive(Debug, Clone)]
+pub struct ParamError {
+    pub field: String,
+    pub message: String,
+    pub allowed: Allowed,
+}
+
+impl ParamError {
+    /// The value is not one of `values`
+    pub fn one_of(field: &str, message: impl Into<String>, values: &[&str]) -> Self {
+        let allowed = Allowed::OneOf(values.iter().map(|v| v.to_string()).collect());
+        Self { field: field.to_string(), message: message.into(), allowed }
+    }
+
+    /// The value lies outside `min..=max`
+    pub fn range(field: &str, message: impl Into<String>, min: impl ToString, max: impl ToString) -> Self {
+        let allowed = Allowed::Range { min: min.to_string(), max: max.to_string() };
+        Self { field: field.to_string(), message: message.into(), allowed }
+    }
+
+    /// The value does not match the expected `format`
+    pub fn format(field: &str, message: impl Into<String>, format: &str) -> Self {
+        Self { field: field.to_string(), message: message.into(), allowed: Allowed::Format(format.to_string()) }
+    }
+}
+
+/// A failed ffmpeg run, with a sanitized excerpt of its log
+#[derive(Debug, Clone)]
+pub struct ToolFailure {
+    pub message: String,
+    /// Last lines of ffmpeg's stderr with temp paths and addresses removed
+    pub diagnostic: Option<String>,
+}
+
 /// Application-specific error types
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
     
+    #[error("Invalid parameter {}: {}", .0.field, .0.message)]
+    InvalidParameter(ParamError),
+
     #[error("Processing error: {0}")]
     ProcessingError(String),
+
+    #[error("Output larger: {0}")]
+    OutputLarger(String),
+
+    #[error("Invalid media: {}", .0.message)]
+    InvalidMedia(ToolFailure),
+
+    #[error("Unsupported codec: {}", .0.message)]
+    UnsupportedCodec(ToolFailure),
+
+    #[error("No usable streams: {}", .0.message)]
+    NoStreams(ToolFailure),
+
+    #[error("Encoder unavailable: {}", .0.message)]
+    EncoderUnavailable(ToolFailure),
+
+    #[error("Insufficient storage: {}", .0.message)]
+    InsufficientStorage(ToolFailure),
+
+    #[error("ffmpeg failed: {}", .0.message)]
+    FfmpegFailed(ToolFailure),
+
+    #[error("ffmpeg unavailable: {0}")]
+    FfmpegUnavailable(String),
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
+    #[error("Server busy: {message}")]
+    ServerBusy { message: String, retry_after_secs: u64 },
     
     #[error("Internal server error: {0}")]
     InternalError(String),
@@ -21,25 +108,72 @@ pub enum AppError {
     NotFound(String),
 }
 
+impl From<ParamError> for AppError {
+    fn from(error: ParamError) -> Self {
+        AppError::InvalidParameter(error)
+    }
+}
+
 /// Convert AppError to HTTP response
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
+        let mut param = None;
+        let mut diagnostic = None;
+        let mut retry_after = None;
+        let mut tool_failure = |failure: ToolFailure| {
+            diagnostic = failure.diagnostic;
+            failure.message
+        };
         let (status, error_message, error_code) = match self {
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg, "BAD_REQUEST"),
+            AppError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg, "PAYLOAD_TOO_LARGE"),
+            AppError::InvalidParameter(error) => {
+                let message = error.message.clone();
+                param = Some(error);
+                (StatusCode::BAD_REQUEST, message, "INVALID_PARAMETER")
+            }
             AppError::ProcessingError(msg) => (StatusCode::UNPROCESSABLE_ENTITY, msg, "PROCESSING_ERROR"),
+            AppError::OutputLarger(msg) => (StatusCode::UNPROCESSABLE_ENTITY, msg, "OUTPUT_LARGER"),
+            AppError::InvalidMedia(f) => (StatusCode::UNPROCESSABLE_ENTITY, tool_failure(f), "INVALID_MEDIA"),
+            AppError::UnsupportedCodec(f) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, tool_failure(f), "UNSUPPORTED_CODEC"),
+            AppError::NoStreams(f) => (StatusCode::UNPROCESSABLE_ENTITY, tool_failure(f), "NO_STREAMS"),
+            AppError::EncoderUnavailable(f) => (StatusCode::NOT_IMPLEMENTED, tool_failure(f), "ENCODER_UNAVAILABLE"),
+            AppError::InsufficientStorage(f) => (StatusCode::INSUFFICIENT_STORAGE, tool_failure(f), "INSUFFICIENT_STORAGE"),
+            AppError::FfmpegFailed(f) => (StatusCode::INTERNAL_SERVER_ERROR, tool_failure(f), "FFMPEG_FAILED"),
+            AppError::FfmpegUnavailable(msg) => (StatusCode::NOT_IMPLEMENTED, msg, "FFMPEG_UNAVAILABLE"),
+            AppError::Timeout(msg) => (StatusCode::GATEWAY_TIMEOUT, msg, "TIMEOUT"),
+            AppError::Cancelled(msg) => (StatusCode::CONFLICT, msg, "CANCELLED"),
+            AppError::ServerBusy { message, retry_after_secs } => {
+                retry_after = Some(retry_after_secs);
+                (StatusCode::SERVICE_UNAVAILABLE, message, "SERVER_BUSY")
+            }
             AppError::InternalError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg, "INTERNAL_ERROR"),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg, "NOT_FOUND"),
         };
 
         tracing::error!("❌ API Error: {} - {}", error_code, error_message);
 
-        let body = Json(json!({
+        let mut body = json!({
             "success": false,
             "error": error_message,
             "code": error_code,
             "timestamp": chrono::Utc::now().to_rfc3339()
-        }));
+        });
+        if let Some(param) = param {
+            body["field"] = json!(param.field);
+            body["allowed"] = json!(param.allowed);
+        }
+        if let Some(diagnostic) = diagnostic {
+            body["diagnostic"] = json!(diagnostic);
+        }
+        if let Some(secs) = retry_after {
+            body["retry_after_secs"] = json!(secs);
+        }
 
-        (status, body).into_response()
+        let mut response = (status, Json(body)).into_response();
+        if let Some(secs) = retry_after {
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
 }
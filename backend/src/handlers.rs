@@ -1,33 +1,103 @@
 use axum::{
-    extract::Multipart,
-    response::Json,
+    body::Body,
+    extract::{Multipart, Path, Query, State},
+    http::{
+        header::{ACCEPT, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap, HeaderName, HeaderValue,
+    },
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
 };
+use futures::stream::{self, Stream, StreamExt};
+use tokio_util::io::ReaderStream;
+use std::convert::Infallible;
+use tokio::sync::OwnedSemaphorePermit;
 use serde_json::{json, Value};
 use base64::{Engine as _, engine::general_purpose};
 use crate::services::image_processor;
+use crate::services::jobs::{self, JobHandle};
+use crate::services::pool::WorkerPool;
+use crate::services::upload::{self, Upload};
 use crate::services::video_processor;
-use crate::models::ConvertResponse;
-use crate::errors::AppError;
+use crate::models::{
+    parse_bitrate_kbps, parse_timestamp, AudioFormat, AudioSettings, AudioSource, ConvertResponse, ImageConversionSettings,
+    ImageFilter, InspectResponse, JobStatus, Loudnorm, PadSettings, ParamChoice, PosterFrame, RateControl, ResponseMode, ResponseQuery, ScoreSummary, StreamInfo,
+    TrimSegment, VideoBitrate,
+};
+use crate::errors::{AppError, ParamError};
+use crate::state::AppState;
+
+/// Accepted timestamp notations, as reported in validation errors
+const TIMESTAMP_FORMAT: &str = "seconds (12.5), MM:SS or HH:MM:SS.fff";
+const POSTER_FORMAT: &str = "auto, or a timestamp in seconds (12.5), MM:SS or HH:MM:SS.fff";
+const SEGMENTS_FORMAT: &str = "comma-separated start-end timestamp ranges, e.g. 0:05-0:12,1:30-1:45";
+const PAD_FORMAT: &str = "W:H aspect ratio (16:9) or WxH size (1200x1200)";
+const PAD_COLOR_FORMAT: &str = "#RRGGBB, #RRGGBBAA or transparent";
+const FILTERS_FORMAT: &str = "comma-separated name[:param[:param]] chain of at most 16 of sharpen, blur, brightness, contrast, gamma, saturation, grayscale, sepia, invert";
+
+/// Convert-video fields that only affect the video track and are refused with audio_only
+const VIDEO_ONLY_FIELDS: &[&str] = &[
+    "quality", "video_codec", "video_bitrate", "min_bitrate", "max_bitrate", "max_size_mb", "target_vmaf",
+    "max_width", "max_height", "fps", "scale_algorithm", "start", "end", "duration", "segments",
+    "output_format", "loop", "palette_colors", "dither", "poster", "quality_metrics",
+];
 
-/// Health check endpoint
-pub async fn health_check() -> Json<Value> {
+/// Health check endpoint, including the load of the worker pools
+pub async fn health_check(State(state): State<AppState>) -> Json<Value> {
+    let pool = |pool: &WorkerPool| json!({ "workers": pool.workers(), "busy": pool.busy(), "queued": pool.queued() });
+    let capabilities = video_processor::capabilities().await;
+    let features: serde_json::Map<String, Value> =
+        capabilities.features().into_iter().map(|(name, supported)| (name.to_string(), json!(supported))).collect();
+    let mut capabilities_json = json!(*capabilities);
+    capabilities_json["features"] = Value::Object(features);
+
+    // Images still work without ffmpeg
+    let status = if capabilities.ffmpeg.available && capabilities.ffprobe.available { "healthy" } else { "degraded" };
     Json(json!({
-        "status": "healthy",
+        "status": status,
         "service": "png-to-webp-converter",
         "version": "0.1.0",
+        "workers": {
+            "image": pool(&state.image_pool),
+            "video": pool(&state.video_pool)
+        },
+        "capabilities": capabilities_json,
         "timestamp": chrono::Utc::now().to_rfc3339()
     }))
 }
 
 /// Convert PNG image to WebP format
 /// 
-/// Accepts multipart/form-data with 'image' field containing PNG file
+/// Accepts multipart/form-data with:
+/// - 'image' field containing PNG file
+/// - Optional 'pad' field with a target aspect ratio ("1:1") or size ("1200x1200")
+/// - Optional 'pad_color' field ("#RRGGBB", "#RRGGBBAA" or "transparent", default white)
+/// - Optional 'pad_gravity' field (center|north|south|east|west|northeast|northwest|southeast|southwest)
+/// - Optional 'filters' field with a comma-separated filter chain applied in order
+///   (e.g. "sharpen:0.5:2,brightness:10,saturation:1.2,grayscale")
+/// - Optional 'mode' field (lossy|lossless|auto, default lossy)
+/// - Optional 'quality' field with lossy quality (0-100, default 85)
+/// - Optional 'min_ssim' field with the SSIM the lossy result must reach in auto mode (0-1, default 0.98)
+/// - Optional 'size_policy' field (always|only-if-smaller|error-if-larger, default always)
+/// - Optional 'color_profile' field (srgb|keep|ignore, default srgb) for embedded ICC profiles
+/// - Optional 'job_id' field to follow the queue position on `/api/jobs/{job_id}/events`
+///
 /// Returns converted WebP image as base64 encoded string
-pub async fn convert_image(mut multipart: Multipart) -> Result<Json<ConvertResponse>, AppError> {
+pub async fn convert_image(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Json<ConvertResponse>, AppError> {
     tracing::info!("🖼️  Received image conversion request");
 
     let mut image_data: Option<Vec<u8>> = None;
     let mut filename: Option<String> = None;
+    let mut settings = ImageConversionSettings::default();
+    let mut pad_target = None;
+    let mut pad_background = None;
+    let mut pad_gravity = None;
+    let mut job_id: Option<String> = None;
 
     // Parse multipart form data
     while let Some(field) = multipart.next_field().await.map_err(|e| {
@@ -46,12 +116,64 @@ pub async fn convert_image(mut multipart: Multipart) -> Result<Json<ConvertRespo
                 image_data = Some(data.to_vec());
                 tracing::info!("📁 Received file: {:?}, size: {} bytes", filename, data.len());
             }
+            "pad" => {
+                let value = read_text_field(field, "pad").await?;
+                pad_target = Some(value.parse().map_err(|message| ParamError::format("pad", message, PAD_FORMAT))?);
+            }
+            "pad_color" => {
+                let value = read_text_field(field, "pad_color").await?;
+                pad_background = Some(value.parse().map_err(|message| ParamError::format("pad_color", message, PAD_COLOR_FORMAT))?);
+            }
+            "pad_gravity" => {
+                let value = read_text_field(field, "pad_gravity").await?;
+                pad_gravity = Some(parse_choice(&value, "pad_gravity")?);
+            }
+            "filters" => {
+                let value = read_text_field(field, "filters").await?;
+                settings.filters = ImageFilter::parse_list(&value).map_err(|message| ParamError::format("filters", message, FILTERS_FORMAT))?;
+            }
+            "mode" => {
+                let value = read_text_field(field, "mode").await?;
+                settings.mode = parse_choice(&value, "mode")?;
+            }
+            "quality" => {
+                let value = read_text_field(field, "quality").await?;
+                settings.quality = parse_in_range(&value, "quality", 0.0, 100.0)?;
+            }
+            "min_ssim" => {
+                let value = read_text_field(field, "min_ssim").await?;
+                settings.min_ssim = parse_in_range(&value, "min_ssim", 0.0, 1.0)?;
+            }
+            "size_policy" => {
+                let value = read_text_field(field, "size_policy").await?;
+                settings.size_policy = parse_choice(&value, "size_policy")?;
+            }
+            "color_profile" => {
+                let value = read_text_field(field, "color_profile").await?;
+                settings.color_profile = parse_choice(&value, "color_profile")?;
+            }
+            "job_id" => {
+                job_id = Some(read_text_field(field, "job_id").await?.trim().to_string());
+            }
             _ => {
                 tracing::warn!("🚫 Ignored unknown field: {}", field_name);
             }
         }
     }
 
+    settings.pad = match pad_target {
+        Some(target) => Some(PadSettings {
+            target,
+            background: pad_background.unwrap_or_default(),
+            gravity: pad_gravity.unwrap_or_default(),
+        }),
+        None if pad_background.is_some() || pad_gravity.is_some() => {
+            return Err(AppError::BadRequest("'pad_color' and 'pad_gravity' require a 'pad' target".to_string()));
+        }
+        None => None,
+    };
+    tracing::info!("🎛️ Image settings applied: {:?}", settings);
+
     // Validate that we received image data
     let image_bytes = image_data.ok_or_else(|| {
         tracing::error!("No image field found in request");
@@ -64,8 +186,42 @@ pub async fn convert_image(mut multipart: Multipart) -> Result<Json<ConvertRespo
         return Err(AppError::BadRequest("File is not a valid PNG image".to_string()));
     }
 
-    // Convert PNG to WebP
-    let webp_data = image_processor::convert_png_to_webp(&image_bytes).await?;
+    // Convert PNG to WebP on the blocking pool; the permit bounds how many run at once
+    let size_policy = settings.size_policy;
+    let job = state.jobs.start(job_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()))?;
+    let job_id = job.id().to_string();
+    let converted = async {
+        let _worker = acquire_worker(&state.image_pool, &job).await?;
+        image_processor::convert_png_to_webp_async(image_bytes, settings).await
+    };
+    let (output, image_bytes) = match converted.await {
+        Ok(converted) => converted,
+        Err(e) => {
+            job.fail(&e);
+            return Err(e);
+        }
+    };
+    let webp_data = output.webp_data;
+
+    // Apply the output size policy
+    let converted = match size_policy.keep_converted(image_bytes.len(), webp_data.len()).map_err(AppError::OutputLarger) {
+        Ok(converted) => {
+            job.complete();
+            converted
+        }
+        Err(e) => {
+            job.fail(&e);
+            return Err(e);
+        }
+    };
+    if !converted {
+        tracing::info!("↩️  WebP output ({} bytes) is not smaller than the PNG ({} bytes), returning the original",
+                       webp_data.len(), image_bytes.len());
+        let mut response = original_response(filename.unwrap_or_else(|| "original.png".to_string()), image_bytes.len());
+        response.webp_data = general_purpose::STANDARD.encode(&image_bytes);
+        response.job_id = Some(job_id);
+        return Ok(Json(response));
+    }
     
     // Generate output filename
     let original_filename = filename.clone();
@@ -88,6 +244,17 @@ pub async fn convert_image(mut multipart: Multipart) -> Result<Json<ConvertRespo
         original_size: image_bytes.len(),
         converted_size: webp_data.len(),
         compression_ratio: (1.0 - (webp_data.len() as f64 / image_bytes.len() as f64)) * 100.0,
+        converted: true,
+        encoding_mode: Some(output.mode),
+        ssim: output.ssim,
+        source_profile: output.source_profile,
+        job_id: Some(job_id),
+        video_encoder: None,
+        video_bitrate_kbps: None,
+        poster_data: None,
+        input_sha256: None,
+        quality_metrics: None,
+        crf_search: None,
     };
 
     tracing::info!("📡 Sending response: {} bytes -> {} bytes ({}% reduction)", 
@@ -98,65 +265,187 @@ pub async fn convert_image(mut multipart: Multipart) -> Result<Json<ConvertRespo
     Ok(Json(response))
 }
 
-/// Convert MP4 video to WebM
+/// Convert a video to WebM
 ///
 /// Accepts multipart/form-data with:
-/// - 'video' field containing MP4 file
+/// - 'video' field containing an MP4, MOV, MKV, WebM, AVI or GIF file
 /// - Optional 'quality' field with compression quality (maximum|high|balanced|low|minimal)
+/// - Optional 'video_codec' field (vp9|av1|vp8, default vp9)
 /// - Optional 'audio_bitrate' field (e.g., "64k", "96k", "128k")
+/// - Optional 'video_bitrate' field (e.g., "800k", "2M") for a two-pass average bitrate
+///   encode, with optional 'min_bitrate' and 'max_bitrate' bounds
+/// - Optional 'max_size_mb' field to derive the bitrate from a file size limit
+/// - Optional 'target_vmaf' field (50-99) to pick the CRF whose output reaches that mean
+///   VMAF, found by encoding and scoring short samples first (needs ffmpeg with libvmaf)
+/// - Optional 'max_width'/'max_height' fields to scale the video down, keeping its aspect ratio
+/// - Optional 'fps' field capping the output frame rate
+/// - Optional 'scale_algorithm' field (bicubic|bilinear|lanczos|area|neighbor, default bicubic)
+/// - Optional 'start' and 'end' or 'duration' fields (seconds, MM:SS or HH:MM:SS) to trim the video
+/// - Optional 'segments' field with comma separated start-end ranges that are cut and joined
+/// - Optional 'poster' field (auto or a timestamp) to also return a WebP poster frame
+/// - Optional 'quality_metrics' field (true/false) to score the WebM against the source with
+///   VMAF (when ffmpeg has libvmaf), SSIM and PSNR
+/// - Optional 'output_format' field (webm|webp|gif, default webm); webp and gif produce
+///   silent looping animations controlled by 'loop' (plays, 0 = forever), and for gif
+///   'palette_colors' (2-256) and 'dither' (sierra2_4a|bayer|floyd_steinberg|none)
+/// - Optional 'mute' field (true|false) to drop the audio track
+/// - Optional 'opus_application' field (voip|audio|lowdelay, default voip; use audio for music)
+/// - Optional 'audio_channels' (1|2) and 'audio_sample_rate' fields to downmix and resample
+/// - Optional 'loudnorm' field (true, or targets as I[:TP[:LRA]]) for two-pass EBU R128
+///   loudness normalization
+/// - Optional 'audio_only' field (true|false) to drop the video and return audio in
+///   'audio_format' (opus|webm|mp3|aac); video-only fields are rejected with it
+/// - Optional 'size_policy' field (always|only-if-smaller|error-if-larger, default always)
+/// - Optional 'job_id' field (letters, digits, '-' or '_') to follow progress on
+///   `/api/jobs/{job_id}/events`; a random id is assigned when omitted
+///
 /// Returns converted WebM as base64 encoded string
-pub async fn convert_video(mut multipart: Multipart) -> Result<Json<ConvertResponse>, AppError> {
+pub async fn convert_video(
+    State(state): State<AppState>,
+    Query(query): Query<ResponseQuery>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Response, AppError> {
     tracing::info!("🎬 Received video conversion request");
+    let mode = response_mode(&headers, &query)?;
+    // Refuse before reading the upload when ffmpeg is missing
+    video_processor::require_ffmpeg().await?;
 
-    let mut video_data: Option<Vec<u8>> = None;
-    let mut filename: Option<String> = None;
+    let mut video_data: Option<Upload> = None;
     let mut compression_settings = crate::models::VideoCompressionSettings::default();
+    let mut job_id: Option<String> = None;
+    let mut video_bitrate: Option<u32> = None;
+    let mut min_bitrate: Option<u32> = None;
+    let mut max_bitrate: Option<u32> = None;
+    let mut max_size_mb: Option<f64> = None;
+    let mut target_vmaf: Option<f64> = None;
+    let mut trim_start: Option<f64> = None;
+    let mut trim_end: Option<f64> = None;
+    let mut trim_duration: Option<f64> = None;
+    let mut audio_only = false;
+    let mut audio_settings = AudioSettings::default();
+    let mut video_fields: Vec<String> = Vec::new();
 
     while let Some(field) = multipart.next_field().await.map_err(|e| {
         tracing::error!("Failed to parse multipart field: {}", e);
         AppError::BadRequest("Invalid multipart data".to_string())
     })? {
         let field_name = field.name().unwrap_or("unknown");
+        if VIDEO_ONLY_FIELDS.contains(&field_name) && !video_fields.iter().any(|f| f == field_name) {
+            video_fields.push(field_name.to_string());
+        }
 
         match field_name {
             "video" => {
-                filename = field.file_name().map(|s| s.to_string());
-                let data = field.bytes().await.map_err(|e| {
-                    tracing::error!("Failed to read video data: {}", e);
-                    AppError::BadRequest("Failed to read video data".to_string())
-                })?;
-                video_data = Some(data.to_vec());
-                tracing::info!("📁 Received file: {:?}, size: {} bytes", filename, data.len());
+                video_data = Some(upload::receive(field, "video", upload::MAX_MEDIA_BYTES).await?);
             }
             "quality" => {
-                let quality_str = field.text().await.map_err(|e| {
-                    tracing::error!("Failed to read quality setting: {}", e);
-                    AppError::BadRequest("Failed to read quality setting".to_string())
-                })?;
-                
-                tracing::info!("📥 Received quality field: '{}'", quality_str);
-                
-                compression_settings.quality = match quality_str.to_lowercase().as_str() {
-                    "maximum" => crate::models::CompressionQuality::Maximum,
-                    "high" => crate::models::CompressionQuality::High,
-                    "balanced" => crate::models::CompressionQuality::Balanced,
-                    "low" => crate::models::CompressionQuality::Low,
-                    "minimal" => crate::models::CompressionQuality::Minimal,
-                    _ => {
-                        tracing::warn!("Unknown quality setting '{}', using default", quality_str);
-                        crate::models::CompressionQuality::High
-                    }
-                };
-                tracing::info!("🎛️ Quality setting applied: {:?}", compression_settings.quality);
+                let value = read_text_field(field, "quality").await?;
+                compression_settings.quality = parse_choice(&value, "quality")?;
             }
             "audio_bitrate" => {
-                let bitrate = field.text().await.map_err(|e| {
-                    tracing::error!("Failed to read audio bitrate: {}", e);
-                    AppError::BadRequest("Failed to read audio bitrate".to_string())
-                })?;
-                tracing::info!("📥 Received audio_bitrate field: '{}'", bitrate);
-                compression_settings.audio_bitrate = Some(bitrate.clone());
-                tracing::info!("🔊 Audio bitrate applied: {:?}", compression_settings.audio_bitrate);
+                let value = read_text_field(field, "audio_bitrate").await?;
+                apply_audio_field(&mut audio_settings, "audio_bitrate", &value)?;
+            }
+            "size_policy" => {
+                let value = read_text_field(field, "size_policy").await?;
+                compression_settings.size_policy = parse_choice(&value, "size_policy")?;
+            }
+            "video_codec" => {
+                let value = read_text_field(field, "video_codec").await?;
+                compression_settings.video_codec = parse_choice(&value, "video_codec")?;
+            }
+            "audio_only" => {
+                let value = read_text_field(field, "audio_only").await?;
+                audio_only = parse_bool(&value, "audio_only")?;
+            }
+            "audio_format" | "audio_channels" | "audio_sample_rate" | "opus_application" | "loudnorm" => {
+                let name = field_name.to_string();
+                let value = read_text_field(field, &name).await?;
+                apply_audio_field(&mut audio_settings, &name, &value)?;
+            }
+            "mute" => {
+                let value = read_text_field(field, "mute").await?;
+                compression_settings.audio.mute = parse_bool(&value, "mute")?;
+            }
+            "quality_metrics" => {
+                let value = read_text_field(field, "quality_metrics").await?;
+                compression_settings.quality_metrics = parse_bool(&value, "quality_metrics")?;
+            }
+            "video_bitrate" | "min_bitrate" | "max_bitrate" => {
+                let name = field_name.to_string();
+                let value = read_text_field(field, &name).await?;
+                let kbps = parse_bitrate(&value, &name)?;
+                match name.as_str() {
+                    "video_bitrate" => video_bitrate = Some(kbps),
+                    "min_bitrate" => min_bitrate = Some(kbps),
+                    _ => max_bitrate = Some(kbps),
+                }
+            }
+            "max_width" | "max_height" => {
+                let name = field_name.to_string();
+                let value = read_text_field(field, &name).await?;
+                let max = crate::models::VideoCompressionSettings::MAX_DIMENSION;
+                let dimension = Some(parse_in_range(&value, &name, 2, max)?);
+                if name == "max_width" {
+                    compression_settings.max_width = dimension;
+                } else {
+                    compression_settings.max_height = dimension;
+                }
+            }
+            "fps" => {
+                let value = read_text_field(field, "fps").await?;
+                let max = crate::models::VideoCompressionSettings::MAX_FPS;
+                compression_settings.fps = Some(parse_in_range(&value, "fps", 1.0, max)?);
+            }
+            "scale_algorithm" => {
+                let value = read_text_field(field, "scale_algorithm").await?;
+                compression_settings.scale_algorithm = parse_choice(&value, "scale_algorithm")?;
+            }
+            "start" | "end" | "duration" => {
+                let name = field_name.to_string();
+                let value = read_text_field(field, &name).await?;
+                let seconds = Some(parse_time_field(&value, &name)?);
+                match name.as_str() {
+                    "start" => trim_start = seconds,
+                    "end" => trim_end = seconds,
+                    _ => trim_duration = seconds,
+                }
+            }
+            "output_format" => {
+                let value = read_text_field(field, "output_format").await?;
+                compression_settings.output_format = parse_choice(&value, "output_format")?;
+            }
+            "loop" => {
+                let value = read_text_field(field, "loop").await?;
+                compression_settings.loop_count = parse_in_range(&value, "loop", 0, 1000)?;
+            }
+            "palette_colors" => {
+                let value = read_text_field(field, "palette_colors").await?;
+                compression_settings.palette_colors = parse_in_range(&value, "palette_colors", 2, 256)?;
+            }
+            "dither" => {
+                let value = read_text_field(field, "dither").await?;
+                compression_settings.dither = parse_choice(&value, "dither")?;
+            }
+            "poster" => {
+                let value = read_text_field(field, "poster").await?;
+                compression_settings.poster = Some(value.parse().map_err(|message| ParamError::format("poster", message, POSTER_FORMAT))?);
+            }
+            "segments" => {
+                let value = read_text_field(field, "segments").await?;
+                compression_settings.segments = TrimSegment::parse_list(&value).map_err(|message| ParamError::format("segments", message, SEGMENTS_FORMAT))?;
+            }
+            "max_size_mb" => {
+                let value = read_text_field(field, "max_size_mb").await?;
+                max_size_mb = Some(parse_in_range(&value, "max_size_mb", 0.1, 2048.0)?);
+            }
+            "target_vmaf" => {
+                let value = read_text_field(field, "target_vmaf").await?;
+                target_vmaf = Some(parse_in_range(&value, "target_vmaf", RateControl::MIN_TARGET_VMAF, RateControl::MAX_TARGET_VMAF)?);
+            }
+            "job_id" => {
+                job_id = Some(read_text_field(field, "job_id").await?.trim().to_string());
             }
             _ => {
                 tracing::warn!("🚫 Ignored unknown field: {}", field_name);
@@ -164,31 +453,171 @@ pub async fn convert_video(mut multipart: Multipart) -> Result<Json<ConvertRespo
         }
     }
 
-    let video_bytes = video_data.ok_or_else(|| {
+    if audio_only && !video_fields.is_empty() {
+        return Err(AppError::BadRequest(format!("{} cannot be combined with audio_only", video_fields.join(", "))));
+    }
+
+    if trim_start.is_some() || trim_end.is_some() || trim_duration.is_some() {
+        if !compression_settings.segments.is_empty() {
+            return Err(AppError::BadRequest("Use either start/end/duration or segments, not both".to_string()));
+        }
+        let start = trim_start.unwrap_or(0.0);
+        let end = match (trim_end, trim_duration) {
+            (Some(_), Some(_)) => {
+                return Err(AppError::BadRequest("Use either end or duration, not both".to_string()));
+            }
+            (end, duration) => end.or(duration.map(|d| start + d)),
+        };
+        compression_settings.segments = vec![TrimSegment::new(start, end).map_err(AppError::BadRequest)?];
+    }
+
+    if target_vmaf.is_some() && (video_bitrate.is_some() || max_size_mb.is_some()) {
+        return Err(AppError::BadRequest("target_vmaf cannot be combined with video_bitrate or max_size_mb".to_string()));
+    }
+    compression_settings.rate_control = match (video_bitrate, max_size_mb) {
+        (Some(_), Some(_)) => {
+            return Err(AppError::BadRequest("Use either video_bitrate or max_size_mb, not both".to_string()));
+        }
+        (Some(target_kbps), None) => {
+            let bitrate = VideoBitrate { target_kbps, min_kbps: min_bitrate, max_kbps: max_bitrate };
+            bitrate.validate()?;
+            RateControl::Bitrate(bitrate)
+        }
+        (None, _) if min_bitrate.is_some() || max_bitrate.is_some() => {
+            return Err(AppError::BadRequest("min_bitrate and max_bitrate require video_bitrate".to_string()));
+        }
+        (None, Some(max_size_mb)) => RateControl::TargetSize { max_size_mb },
+        (None, None) => target_vmaf.map_or(RateControl::Crf, |vmaf| RateControl::TargetVmaf { vmaf }),
+    };
+
+    if let Some(kbps) = audio_settings.bitrate_kbps {
+        compression_settings.audio.bitrate_kbps = kbps;
+    }
+    compression_settings.audio.application = audio_settings.application.unwrap_or_default();
+    compression_settings.audio.channels = audio_settings.channels;
+    compression_settings.audio.sample_rate = audio_settings.sample_rate;
+    compression_settings.audio.loudnorm = audio_settings.loudnorm;
+    if !audio_only {
+        compression_settings.audio.validate()?;
+    }
+
+    let output_format = compression_settings.output_format;
+    if output_format.is_animation() {
+        if compression_settings.rate_control != RateControl::Crf {
+            return Err(AppError::BadRequest(format!(
+                "video_bitrate, max_size_mb and target_vmaf are not supported for {} output",
+                output_format.name()
+            )));
+        }
+        if compression_settings.poster.is_some() {
+            return Err(AppError::BadRequest(format!("poster is not supported for {} output", output_format.name())));
+        }
+        if compression_settings.quality_metrics {
+            return Err(AppError::BadRequest(format!(
+                "quality_metrics is not supported for {} output",
+                output_format.name()
+            )));
+        }
+    }
+    if mode == ResponseMode::Binary && compression_settings.poster.is_some() {
+        return Err(AppError::BadRequest(
+            "poster is not returned with binary responses, use /api/video-poster instead".to_string(),
+        ));
+    }
+
+    let upload = video_data.ok_or_else(|| {
         tracing::error!("No video field found in request");
         AppError::BadRequest("No video field found".to_string())
     })?;
 
+    // Validate the container before handing the bytes to ffmpeg
+    let container = video_processor::sniff_container(upload.head()).ok_or_else(|| {
+        tracing::error!("🎞️ Unrecognized video container received");
+        AppError::BadRequest("Unsupported video format, expected MP4, MOV, MKV, WebM, AVI or GIF".to_string())
+    })?;
+    tracing::info!("🎞️ Detected container: {}", container.name());
+
+    if audio_only {
+        if compression_settings.audio.mute {
+            return Err(AppError::BadRequest("mute cannot be combined with audio_only".to_string()));
+        }
+        audio_settings.size_policy = compression_settings.size_policy;
+        return convert_audio_job(&state, job_id, upload, AudioSource::Video(container), audio_settings, mode).await;
+    }
+
     // Call service to convert MP4 -> WebM using ffmpeg with compression settings
-    let original_len = video_bytes.len();
-    let webm_data = video_processor::convert_mp4_to_webm_with_settings(video_bytes, compression_settings).await?;
+    let original_len = upload.size as usize;
+    let size_policy = compression_settings.size_policy;
+    let job = state.jobs.start(job_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()))?;
+    let job_id = job.id().to_string();
+    let _worker = match acquire_worker(&state.video_pool, &job).await {
+        Ok(worker) => worker,
+        Err(e) => {
+            job.fail(&e);
+            return Err(e);
+        }
+    };
+    let output = match video_processor::convert_mp4_to_webm_with_progress(upload.path(), container, compression_settings, Some(&job)).await {
+        Ok(output) => output,
+        Err(e) => {
+            job.fail(&e);
+            return Err(e);
+        }
+    };
+    let webm_size = output.webm_size as usize;
+    let video_encoder = output.video_encoder.ffmpeg_name().to_string();
+    let poster_data = output.poster_webp.map(|poster| general_purpose::STANDARD.encode(poster));
 
-    // Generate output filename
-    let output_filename = filename
-        .and_then(|f| f.rsplit_once('.').map(|(s, _)| format!("{}.webm", s)))
-        .unwrap_or_else(|| "converted.webm".to_string());
+    // The size policy can still reject the result, so the job only completes after it
+    let converted = match size_policy.keep_converted(original_len, webm_size).map_err(AppError::OutputLarger) {
+        Ok(converted) => {
+            job.complete();
+            converted
+        }
+        Err(e) => {
+            job.fail(&e);
+            return Err(e);
+        }
+    };
+    if !converted {
+        tracing::info!("↩️  {} output ({} bytes) is not smaller than the input ({} bytes), returning the original",
+                       output_format.name(), webm_size, original_len);
+        let filename = upload.filename.clone().unwrap_or_else(|| format!("original.{}", container.extension()));
+        let mut response = original_response(filename, original_len);
+        response.job_id = Some(job_id);
+        response.input_sha256 = Some(upload.sha256.clone());
+        response.poster_data = poster_data;
+        response.quality_metrics = output.quality_metrics;
+        response.crf_search = output.crf_search;
+        let path = upload.path().to_path_buf();
+        return file_response(mode, response, &path, container.mime_type(), upload).await;
+    }
 
-    // Encode WebM to base64
-    let webm_base64 = general_purpose::STANDARD.encode(&webm_data);
+    // Generate output filename
+    let extension = output_format.extension();
+    let output_filename = upload.filename
+        .and_then(|f| f.rsplit_once('.').map(|(s, _)| format!("{}.{}", s, extension)))
+        .unwrap_or_else(|| format!("converted.{}", extension));
 
     let response = ConvertResponse {
         success: true,
         message: "Video converted successfully".to_string(),
         filename: output_filename,
-        webp_data: webm_base64,
+        webp_data: String::new(),
         original_size: original_len,
-        converted_size: webm_data.len(),
-        compression_ratio: (1.0 - (webm_data.len() as f64 / original_len as f64)) * 100.0,
+        converted_size: webm_size,
+        compression_ratio: (1.0 - (webm_size as f64 / original_len as f64)) * 100.0,
+        converted: true,
+        encoding_mode: None,
+        ssim: None,
+        source_profile: None,
+        job_id: Some(job_id),
+        video_encoder: Some(video_encoder),
+        video_bitrate_kbps: output.video_bitrate_kbps,
+        poster_data,
+        input_sha256: Some(upload.sha256),
+        quality_metrics: output.quality_metrics,
+        crf_search: output.crf_search,
     };
 
     tracing::info!("✅ Video conversion completed: {} bytes -> {} bytes ({}% reduction)", 
@@ -196,5 +625,628 @@ pub async fn convert_video(mut multipart: Multipart) -> Result<Json<ConvertRespo
                    response.converted_size,
                    response.compression_ratio);
 
+    let path = output.webm_file.to_path_buf();
+    file_response(mode, response, &path, output_format.mime_type(), output.webm_file).await
+}
+
+/// Convert an audio file to Opus, MP3 or AAC
+///
+/// Accepts multipart/form-data with:
+/// - 'audio' field containing a WAV, MP3 or FLAC file
+/// - Optional 'audio_format' field (opus|webm|mp3|aac, default opus)
+/// - Optional 'audio_bitrate' field (e.g., "64k"; default 64k for Opus, 128k for MP3/AAC)
+/// - Optional 'audio_channels' field (1 or 2)
+/// - Optional 'audio_sample_rate' field in Hz (e.g., 48000)
+/// - Optional 'opus_application' field (voip|audio|lowdelay) for Opus output
+/// - Optional 'loudnorm' field (true, or targets as I[:TP[:LRA]]) for two-pass EBU R128
+///   loudness normalization
+/// - Optional 'size_policy' field (always|only-if-smaller|error-if-larger, default always)
+/// - Optional 'job_id' field to follow progress on `/api/jobs/{job_id}/events`
+///
+/// Returns the converted audio as base64 encoded string
+pub async fn convert_audio(
+    State(state): State<AppState>,
+    Query(query): Query<ResponseQuery>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Response, AppError> {
+    tracing::info!("🎧 Received audio conversion request");
+    let mode = response_mode(&headers, &query)?;
+    // Refuse before reading the upload when ffmpeg is missing
+    video_processor::require_ffmpeg().await?;
+
+    let mut audio_data: Option<Upload> = None;
+    let mut settings = AudioSettings::default();
+    let mut job_id: Option<String> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        tracing::error!("Failed to parse multipart field: {}", e);
+        AppError::BadRequest("Invalid multipart data".to_string())
+    })? {
+        let field_name = field.name().unwrap_or("unknown");
+
+        match field_name {
+            "audio" => {
+                audio_data = Some(upload::receive(field, "audio", upload::MAX_MEDIA_BYTES).await?);
+            }
+            "audio_format" | "audio_bitrate" | "audio_channels" | "audio_sample_rate" | "opus_application"
+            | "loudnorm" => {
+                let name = field_name.to_string();
+                let value = read_text_field(field, &name).await?;
+                apply_audio_field(&mut settings, &name, &value)?;
+            }
+            "size_policy" => {
+                let value = read_text_field(field, "size_policy").await?;
+                settings.size_policy = parse_choice(&value, "size_policy")?;
+            }
+            "job_id" => {
+                job_id = Some(read_text_field(field, "job_id").await?.trim().to_string());
+            }
+            _ => {
+                tracing::warn!("🚫 Ignored unknown field: {}", field_name);
+            }
+        }
+    }
+
+    let upload = audio_data.ok_or_else(|| {
+        tracing::error!("No audio field found in request");
+        AppError::BadRequest("No audio field found".to_string())
+    })?;
+
+    let container = video_processor::sniff_audio_container(upload.head()).ok_or_else(|| {
+        tracing::error!("🎧 Unrecognized audio container received");
+        AppError::BadRequest("Unsupported audio format, expected WAV, MP3 or FLAC".to_string())
+    })?;
+    tracing::info!("🎧 Detected container: {}", container.name());
+
+    convert_audio_job(&state, job_id, upload, AudioSource::Audio(container), settings, mode).await
+}
+
+/// Run the audio pipeline as a tracked job and build the response
+async fn convert_audio_job(
+    state: &AppState,
+    job_id: Option<String>,
+    input: Upload,
+    source: AudioSource,
+    settings: AudioSettings,
+    mode: ResponseMode,
+) -> Result<Response, AppError> {
+    settings.validate()?;
+
+    let original_len = input.size as usize;
+    let job = state.jobs.start(job_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()))?;
+    let job_id = job.id().to_string();
+    let _worker = match acquire_worker(&state.video_pool, &job).await {
+        Ok(worker) => worker,
+        Err(e) => {
+            job.fail(&e);
+            return Err(e);
+        }
+    };
+    let output = match video_processor::convert_audio(input.path(), source, &settings, Some(&job)).await {
+        Ok(output) => output,
+        Err(e) => {
+            job.fail(&e);
+            return Err(e);
+        }
+    };
+    let audio_size = output.audio_size as usize;
+
+    let converted = match settings.size_policy.keep_converted(original_len, audio_size).map_err(AppError::OutputLarger) {
+        Ok(converted) => {
+            job.complete();
+            converted
+        }
+        Err(e) => {
+            job.fail(&e);
+            return Err(e);
+        }
+    };
+    if !converted {
+        tracing::info!("↩️  {} output ({} bytes) is not smaller than the input ({} bytes), returning the original",
+                       settings.format.name(), audio_size, original_len);
+        let filename = input.filename.clone().unwrap_or_else(|| "original".to_string());
+        let mut response = original_response(filename, original_len);
+        response.job_id = Some(job_id);
+        response.input_sha256 = Some(input.sha256.clone());
+        let path = input.path().to_path_buf();
+        return file_response(mode, response, &path, source.mime_type(), input).await;
+    }
+
+    let extension = settings.format.extension();
+    let output_filename = input.filename
+        .and_then(|f| f.rsplit_once('.').map(|(s, _)| format!("{}.{}", s, extension)))
+        .unwrap_or_else(|| format!("converted.{}", extension));
+
+    let response = ConvertResponse {
+        success: true,
+        message: "Audio converted successfully".to_string(),
+        filename: output_filename,
+        webp_data: String::new(),
+        original_size: original_len,
+        converted_size: audio_size,
+        compression_ratio: (1.0 - (audio_size as f64 / original_len as f64)) * 100.0,
+        converted: true,
+        encoding_mode: None,
+        ssim: None,
+        source_profile: None,
+        job_id: Some(job_id),
+        video_encoder: None,
+        video_bitrate_kbps: None,
+        poster_data: None,
+        input_sha256: Some(input.sha256),
+        quality_metrics: None,
+        crf_search: None,
+    };
+
+    tracing::info!("✅ Audio conversion completed: {} bytes -> {} bytes at {}k ({}% reduction)",
+                   response.original_size,
+                   response.converted_size,
+                   output.bitrate_kbps,
+                   response.compression_ratio);
+
+    let path = output.audio_file.to_path_buf();
+    file_response(mode, response, &path, settings.format.mime_type(), output.audio_file).await
+}
+
+/// Extract a poster frame from a video as WebP
+///
+/// Accepts multipart/form-data with:
+/// - 'video' field containing an MP4, MOV, MKV, WebM, AVI or GIF file
+/// - Optional 'at' field: a timestamp, or auto (default) for the most representative frame near the start
+/// - Optional 'quality' field (0-100, default 85)
+/// - Optional 'job_id' field to follow the queue position on `/api/jobs/{job_id}/events`
+///
+/// Returns the poster as base64 encoded WebP
+pub async fn video_poster(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Json<ConvertResponse>, AppError> {
+    tracing::info!("🖼️  Received poster extraction request");
+    // Refuse before reading the upload when ffmpeg is missing
+    video_processor::require_ffmpeg().await?;
+
+    let mut video_data: Option<Upload> = None;
+    let mut poster = PosterFrame::Auto;
+    let mut settings = ImageConversionSettings::default();
+    let mut job_id: Option<String> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        tracing::error!("Failed to parse multipart field: {}", e);
+        AppError::BadRequest("Invalid multipart data".to_string())
+    })? {
+        let field_name = field.name().unwrap_or("unknown");
+
+        match field_name {
+            "video" => {
+                video_data = Some(upload::receive(field, "video", upload::MAX_MEDIA_BYTES).await?);
+            }
+            "at" => {
+                let value = read_text_field(field, "at").await?;
+                poster = value.parse().map_err(|message| ParamError::format("at", message, POSTER_FORMAT))?;
+            }
+            "quality" => {
+                let value = read_text_field(field, "quality").await?;
+                settings.quality = parse_in_range(&value, "quality", 0.0, 100.0)?;
+            }
+            "job_id" => {
+                job_id = Some(read_text_field(field, "job_id").await?.trim().to_string());
+            }
+            _ => {
+                tracing::warn!("🚫 Ignored unknown field: {}", field_name);
+            }
+        }
+    }
+
+    let upload = video_data.ok_or_else(|| {
+        tracing::error!("No video field found in request");
+        AppError::BadRequest("No video field found".to_string())
+    })?;
+
+    let container = video_processor::sniff_container(upload.head()).ok_or_else(|| {
+        tracing::error!("🎞️ Unrecognized video container received");
+        AppError::BadRequest("Unsupported video format, expected MP4, MOV, MKV, WebM, AVI or GIF".to_string())
+    })?;
+
+    let original_len = upload.size as usize;
+    let job = state.jobs.start(job_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()))?;
+    let job_id = job.id().to_string();
+    let extracted = async {
+        let _worker = acquire_worker(&state.video_pool, &job).await?;
+        video_processor::extract_poster(upload.path(), container, poster, settings).await
+    };
+    let output = match extracted.await {
+        Ok(output) => {
+            job.complete();
+            output
+        }
+        Err(e) => {
+            job.fail(&e);
+            return Err(e);
+        }
+    };
+
+    let output_filename = upload.filename
+        .and_then(|f| f.rsplit_once('.').map(|(s, _)| format!("{}.webp", s)))
+        .unwrap_or_else(|| "poster.webp".to_string());
+
+    let response = ConvertResponse {
+        success: true,
+        message: "Poster frame extracted successfully".to_string(),
+        filename: output_filename,
+        webp_data: general_purpose::STANDARD.encode(&output.webp_data),
+        original_size: original_len,
+        converted_size: output.webp_data.len(),
+        compression_ratio: (1.0 - (output.webp_data.len() as f64 / original_len as f64)) * 100.0,
+        converted: true,
+        encoding_mode: Some(output.mode),
+        ssim: None,
+        source_profile: None,
+        job_id: Some(job_id),
+        video_encoder: None,
+        video_bitrate_kbps: None,
+        poster_data: None,
+        input_sha256: Some(upload.sha256),
+        quality_metrics: None,
+        crf_search: None,
+    };
+
+    tracing::info!("✅ Poster frame extracted: {} bytes", response.converted_size);
+
     Ok(Json(response))
 }
+
+/// Inspect a video with ffprobe
+///
+/// Accepts multipart/form-data with:
+/// - 'video' field containing an MP4, MOV, MKV, WebM, AVI or GIF file
+/// - Optional 'audio_bitrate' field used for the size estimates (default "64k")
+/// - Optional 'job_id' field to follow the queue position on `/api/jobs/{job_id}/events`
+///
+/// Returns container, duration, bitrate and per-stream details, plus an
+/// estimated WebM size for each quality level
+pub async fn inspect_video(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Json<InspectResponse>, AppError> {
+    tracing::info!("🔍 Received video inspect request");
+    video_processor::require_ffprobe().await?;
+
+    let mut video_data: Option<Upload> = None;
+    let mut audio_kbps: u32 = 64;
+    let mut job_id: Option<String> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        tracing::error!("Failed to parse multipart field: {}", e);
+        AppError::BadRequest("Invalid multipart data".to_string())
+    })? {
+        let field_name = field.name().unwrap_or("unknown");
+
+        match field_name {
+            "video" => {
+                video_data = Some(upload::receive(field, "video", upload::MAX_MEDIA_BYTES).await?);
+            }
+            "audio_bitrate" => {
+                let value = read_text_field(field, "audio_bitrate").await?;
+                let mut audio = AudioSettings { format: AudioFormat::WebM, ..AudioSettings::default() };
+                apply_audio_field(&mut audio, "audio_bitrate", &value)?;
+                audio.validate()?;
+                audio_kbps = audio.bitrate_kbps.unwrap_or(audio_kbps);
+            }
+            "job_id" => {
+                job_id = Some(read_text_field(field, "job_id").await?.trim().to_string());
+            }
+            _ => {
+                tracing::warn!("🚫 Ignored unknown field: {}", field_name);
+            }
+        }
+    }
+
+    let upload = video_data.ok_or_else(|| {
+        tracing::error!("No video field found in request");
+        AppError::BadRequest("No video field found".to_string())
+    })?;
+
+    let container = video_processor::sniff_container(upload.head()).ok_or_else(|| {
+        tracing::error!("🎞️ Unrecognized video container received");
+        AppError::BadRequest("Unsupported video format, expected MP4, MOV, MKV, WebM, AVI or GIF".to_string())
+    })?;
+
+    let job = state.jobs.start(job_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()))?;
+    let job_id = job.id().to_string();
+    let inspected = async {
+        let _worker = acquire_worker(&state.video_pool, &job).await?;
+        video_processor::inspect_video(upload.path(), container).await
+    };
+    let probe = match inspected.await {
+        Ok(probe) => {
+            job.complete();
+            probe
+        }
+        Err(e) => {
+            job.fail(&e);
+            return Err(e);
+        }
+    };
+    let estimates = video_processor::estimate_sizes(&probe, upload.size as usize, audio_kbps);
+
+    let response = InspectResponse {
+        success: true,
+        filename: upload.filename,
+        size: upload.size as usize,
+        sha256: upload.sha256,
+        container: container.name().to_string(),
+        format_name: probe.format.as_ref().and_then(|f| f.format_name.clone()),
+        duration_secs: probe.duration_secs(),
+        bitrate_kbps: probe.bitrate_kbps(),
+        streams: probe.streams.iter().map(StreamInfo::from).collect(),
+        estimates,
+        job_id,
+    };
+
+    tracing::info!("✅ Inspected {}: {:?}s, {} streams", response.container, response.duration_secs, response.streams.len());
+
+    Ok(Json(response))
+}
+
+/// Stream progress of a conversion job as Server-Sent Events
+///
+/// Clients may subscribe before the job starts; the stream emits the current
+/// snapshot, then every update, and closes after the job completes, fails or is cancelled.
+/// A stream whose job does not start within `PENDING_JOB_TTL` is closed.
+pub async fn job_events(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let receiver = state.jobs.subscribe(&job_id)?;
+    tracing::info!("📡 Client subscribed to job {}", job_id);
+
+    let events = stream::unfold((receiver, true, false), |(mut receiver, first, done)| async move {
+        if done {
+            return None;
+        }
+        if !first {
+            let changed = if receiver.borrow().status == JobStatus::Pending {
+                tokio::time::timeout(jobs::PENDING_JOB_TTL, receiver.changed()).await.ok()
+            } else {
+                Some(receiver.changed().await)
+            };
+            if !matches!(changed, Some(Ok(()))) {
+                return None;
+            }
+        }
+
+        let progress = receiver.borrow_and_update().clone();
+        let done = progress.status.is_terminal();
+        let event = Event::default()
+            .event("progress")
+            .json_data(&progress)
+            .unwrap_or_else(|_| Event::default().event("progress"));
+
+        Some((Ok(event), (receiver, false, done)))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Cancel a running conversion job
+///
+/// Kills the job's ffmpeg process and removes its temp files. The request
+/// that started the job fails with `409 CANCELLED`.
+pub async fn cancel_job(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    state.jobs.cancel(&job_id)?;
+    Ok(Json(json!({
+        "success": true,
+        "job_id": job_id,
+        "message": "Cancellation requested"
+    })))
+}
+
+/// Wait for a free worker, publishing the queue position on the job's progress stream
+async fn acquire_worker(pool: &WorkerPool, job: &JobHandle) -> Result<OwnedSemaphorePermit, AppError> {
+    let worker = tokio::select! {
+        worker = pool.acquire(|position| job.queued(position)) => worker?,
+        _ = job.cancelled() => return Err(AppError::Cancelled("Job was cancelled while queued".to_string())),
+    };
+    job.running();
+    Ok(worker)
+}
+
+/// Pick JSON or binary output from `?response=` or, failing that, the Accept header
+fn response_mode(headers: &HeaderMap, query: &ResponseQuery) -> Result<ResponseMode, AppError> {
+    if let Some(value) = &query.response {
+        return parse_choice(value, "response");
+    }
+    let accepts_binary = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media| media.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case("application/octet-stream"));
+    Ok(if accepts_binary { ResponseMode::Binary } else { ResponseMode::Json })
+}
+
+/// Send a converted (or original) file on disk as JSON with base64 data, or stream it as the body
+///
+/// `temp` owns the file and is dropped, deleting it, once the body has been sent.
+async fn file_response(
+    mode: ResponseMode,
+    mut response: ConvertResponse,
+    path: &std::path::Path,
+    content_type: &'static str,
+    temp: impl Send + Sync + 'static,
+) -> Result<Response, AppError> {
+    if mode == ResponseMode::Json {
+        let data = tokio::fs::read(path)
+            .await
+            .map_err(|e| AppError::ProcessingError(format!("Failed to read output file: {}", e)))?;
+        response.webp_data = general_purpose::STANDARD.encode(&data);
+        drop(temp);
+        return Ok(Json(response).into_response());
+    }
+
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| AppError::ProcessingError(format!("Failed to open output file: {}", e)))?;
+    let stream = ReaderStream::new(file).map(move |chunk| {
+        let _temp = &temp;
+        chunk
+    });
+
+    let mut headers = vec![
+        (CONTENT_TYPE, content_type.to_string()),
+        (CONTENT_LENGTH, response.converted_size.to_string()),
+        (CONTENT_DISPOSITION, content_disposition(&response.filename)),
+        (HeaderName::from_static("x-original-size"), response.original_size.to_string()),
+        (HeaderName::from_static("x-converted-size"), response.converted_size.to_string()),
+        (HeaderName::from_static("x-compression-ratio"), format!("{:.2}", response.compression_ratio)),
+        (HeaderName::from_static("x-converted"), response.converted.to_string()),
+    ];
+    let metrics = response.quality_metrics.as_ref();
+    let mean = |score: Option<&ScoreSummary>| score.map(|score| score.mean.to_string());
+    let optional = [
+        ("x-job-id", response.job_id),
+        ("x-video-encoder", response.video_encoder),
+        ("x-video-bitrate-kbps", response.video_bitrate_kbps.map(|kbps| kbps.to_string())),
+        ("x-video-crf", response.crf_search.as_ref().map(|search| search.crf.to_string())),
+        ("x-input-sha256", response.input_sha256),
+        ("x-vmaf-mean", mean(metrics.and_then(|m| m.vmaf.as_ref()))),
+        ("x-ssim-mean", mean(metrics.and_then(|m| m.ssim.as_ref()))),
+        ("x-psnr-mean", mean(metrics.and_then(|m| m.psnr.as_ref()))),
+    ];
+    headers.extend(optional.into_iter().filter_map(|(name, value)| Some((HeaderName::from_static(name), value?))));
+
+    let mut reply = Body::from_stream(stream).into_response();
+    for (name, value) in headers {
+        let value = HeaderValue::from_str(&value)
+            .map_err(|_| AppError::InternalError(format!("Invalid {} header value", name)))?;
+        reply.headers_mut().insert(name, value);
+    }
+    Ok(reply)
+}
+
+/// `attachment` disposition with an ASCII fallback name and the UTF-8 name percent-encoded
+fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if (c.is_ascii_graphic() || c == ' ') && c != '"' && c != '\\' { c } else { '_' })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+}
+
+/// Build the response returned when the size policy keeps the original file
+///
+/// `webp_data` is left empty for the caller to fill in.
+fn original_response(filename: String, original_size: usize) -> ConvertResponse {
+    ConvertResponse {
+        success: true,
+        message: "Converted output was not smaller than the original, returning the original file".to_string(),
+        filename,
+        webp_data: String::new(),
+        original_size,
+        converted_size: original_size,
+        compression_ratio: 0.0,
+        converted: false,
+        encoding_mode: None,
+        ssim: None,
+        source_profile: None,
+        job_id: None,
+        video_encoder: None,
+        video_bitrate_kbps: None,
+        poster_data: None,
+        input_sha256: None,
+        quality_metrics: None,
+        crf_search: None,
+    }
+}
+
+/// Apply one of the `audio_*` fields shared by the audio and video endpoints
+fn apply_audio_field(settings: &mut AudioSettings, name: &str, value: &str) -> Result<(), AppError> {
+    match name {
+        "audio_format" => settings.format = parse_choice(value, name)?,
+        "audio_bitrate" => settings.bitrate_kbps = Some(parse_bitrate(value, name)?),
+        "audio_channels" => settings.channels = Some(parse_in_range(value, name, 1, 2)?),
+        "audio_sample_rate" => settings.sample_rate = Some(parse_in_range(value, name, 8000, 96000)?),
+        "opus_application" => settings.application = Some(parse_choice(value, name)?),
+        "loudnorm" => {
+            settings.loudnorm = match value.trim().to_lowercase().as_str() {
+                "false" | "0" | "no" | "off" | "" => None,
+                "true" | "1" | "yes" | "on" | "ebu" => Some(Loudnorm::default()),
+                targets => Some(
+                    targets
+                        .parse()
+                        .map_err(|message| ParamError::format(name, message, "true, false or I[:TP[:LRA]] in LUFS, dBTP and LU"))?,
+                ),
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Parse a boolean form field ("true"/"false", "1"/"0", "yes"/"no")
+fn parse_bool(value: &str, name: &str) -> Result<bool, AppError> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" | "" => Ok(false),
+        _ => {
+            let message = format!("Invalid {} value '{}', expected true or false", name, value);
+            Err(ParamError::one_of(name, message, &["true", "false"]).into())
+        }
+    }
+}
+
+/// Read a multipart field as text, mapping failures to a bad request
+async fn read_text_field(field: axum::extract::multipart::Field<'_>, name: &str) -> Result<String, AppError> {
+    let value = field.text().await.map_err(|e| {
+        tracing::error!("Failed to read {} field: {}", name, e);
+        AppError::BadRequest(format!("Failed to read {} field", name))
+    })?;
+    tracing::info!("📥 Received {} field: '{}'", name, value);
+    Ok(value)
+}
+
+/// Parse a numeric text field and check it lies within the allowed range
+fn parse_in_range<T>(value: &str, name: &str, min: T, max: T) -> Result<T, AppError>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+{
+    let parsed = value
+        .trim()
+        .parse::<T>()
+        .map_err(|_| ParamError::range(name, format!("Invalid {} value '{}'", name, value), &min, &max))?;
+
+    // Written as a negated range check so NaN is rejected too
+    if !(parsed >= min && parsed <= max) {
+        return Err(ParamError::range(name, format!("{} must be between {} and {}", name, min, max), min, max).into());
+    }
+    Ok(parsed)
+}
+
+/// Parse a field with a fixed set of accepted names
+fn parse_choice<T: ParamChoice>(value: &str, name: &str) -> Result<T, AppError> {
+    value.parse().map_err(|message| ParamError::one_of(name, message, T::ALLOWED).into())
+}
+
+/// Parse a bitrate field such as "64k" or "2M" into kbit/s
+fn parse_bitrate(value: &str, name: &str) -> Result<u32, AppError> {
+    parse_bitrate_kbps(value).ok_or_else(|| {
+        let message = format!("Invalid {} '{}', expected e.g. 64k or 2M", name, value.trim());
+        ParamError::format(name, message, "bits per second, optionally with a k or M suffix").into()
+    })
+}
+
+/// Parse a timestamp field
+fn parse_time_field(value: &str, name: &str) -> Result<f64, AppError> {
+    parse_timestamp(value).map_err(|message| ParamError::format(name, message, TIMESTAMP_FORMAT).into())
+}
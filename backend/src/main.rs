@@ -1,21 +1,23 @@
 use axum::{
     extract::DefaultBodyLimit,
     http::{
-        header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
-        HeaderValue, Method,
+        header::{ACCEPT, AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderName, HeaderValue, Method,
     },
     routing::{get, post},
     Router,
 };
-use handlers::convert_video;
+use handlers::{cancel_job, convert_audio, convert_video, inspect_video, job_events, video_poster};
 use std::net::SocketAddr;
 use tower_http::cors::CorsLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod config;
 mod handlers;
 mod services;
 mod models;
 mod errors;
+mod state;
 
 use handlers::{health_check, convert_image};
 
@@ -33,6 +35,29 @@ async fn main() -> anyhow::Result<()> {
     // Load environment variables
     dotenvy::dotenv().ok();
 
+    let ffmpeg_config = config::ffmpeg();
+    tracing::info!("⏱️ ffmpeg timeout: {:?} + {}x input duration, at most {:?}",
+                   ffmpeg_config.timeout_base, ffmpeg_config.timeout_factor, ffmpeg_config.timeout_max);
+
+    let capabilities = services::video_processor::capabilities().await;
+    if capabilities.ffmpeg.available && capabilities.ffprobe.available {
+        tracing::info!("🎞️ ffmpeg {} found, encoders: {}",
+                       capabilities.ffmpeg.version.as_deref().unwrap_or("(unknown version)"),
+                       capabilities.encoders.join(", "));
+        let missing: Vec<&str> = capabilities.features().into_iter().filter(|(_, ok)| !ok).map(|(name, _)| name).collect();
+        if !missing.is_empty() {
+            tracing::warn!("⚠️ Unsupported by this ffmpeg build: {}", missing.join(", "));
+        }
+    } else {
+        tracing::warn!("⚠️ ffmpeg ('{}') or ffprobe ('{}') is not usable, video and audio endpoints will return 501",
+                       ffmpeg_config.ffmpeg_path, ffmpeg_config.ffprobe_path);
+    }
+
+    let workers = config::WorkerConfig::from_env();
+    tracing::info!("👷 Workers: {} image (queue {}), {} video (queue {})",
+                   workers.image_workers, workers.image_queue, workers.video_workers, workers.video_queue);
+    let state = state::AppState::new(&workers);
+
     // Configure CORS - Allow frontend to communicate with backend
     let cors = CorsLayer::new()
         .allow_origin([
@@ -43,6 +68,22 @@ async fn main() -> anyhow::Result<()> {
         ])
         .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
         .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE])
+        // Metrics of binary responses travel in headers the browser hides by default
+        .expose_headers([
+            CONTENT_DISPOSITION,
+            HeaderName::from_static("x-original-size"),
+            HeaderName::from_static("x-converted-size"),
+            HeaderName::from_static("x-compression-ratio"),
+            HeaderName::from_static("x-converted"),
+            HeaderName::from_static("x-job-id"),
+            HeaderName::from_static("x-video-encoder"),
+            HeaderName::from_static("x-video-bitrate-kbps"),
+            HeaderName::from_static("x-video-crf"),
+            HeaderName::from_static("x-input-sha256"),
+            HeaderName::from_static("x-vmaf-mean"),
+            HeaderName::from_static("x-ssim-mean"),
+            HeaderName::from_static("x-psnr-mean"),
+        ])
         .allow_credentials(true);
 
     // Build application with routes
@@ -50,6 +91,12 @@ async fn main() -> anyhow::Result<()> {
         .route("/health", get(health_check))
     .route("/api/convert", post(convert_image))
     .route("/api/convert-video", post(convert_video))
+        .route("/api/convert-audio", post(convert_audio))
+        .route("/api/video-poster", post(video_poster))
+        .route("/api/inspect-video", post(inspect_video))
+        .route("/api/jobs/:job_id/events", get(job_events))
+        .route("/api/jobs/:job_id/cancel", post(cancel_job))
+        .with_state(state)
         .layer(cors)
     .layer(DefaultBodyLimit::max(200 * 1024 * 1024)); // 200MB max file size
 
@@ -1,5 +1,13 @@
 use serde::{Deserialize, Serialize};
 
+use crate::errors::ParamError;
+
+/// A request parameter with a fixed set of accepted names
+pub trait ParamChoice: std::str::FromStr<Err = String> {
+    /// Accepted names, as listed in error responses
+    const ALLOWED: &'static [&'static str];
+}
+
 /// Response model for successful image conversion
 #[derive(Debug, Serialize)]
 pub struct ConvertResponse {
@@ -10,33 +18,2020 @@ pub struct ConvertResponse {
     pub original_size: usize,
     pub converted_size: usize,
     pub compression_ratio: f64,  // Percentage saved
+    pub converted: bool,  // False when the original file is returned unchanged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding_mode: Option<WebpMode>,  // WebP mode actually used for the output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssim: Option<f64>,  // Structural similarity of the output (auto mode only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_profile: Option<String>,  // Name of the embedded ICC profile in the input
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,  // Job id used for progress and queue position reporting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_encoder: Option<String>,  // ffmpeg encoder actually used (video only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_bitrate_kbps: Option<u32>,  // Target video bitrate in bitrate/target-size mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poster_data: Option<String>,  // Base64 encoded WebP poster frame of the output video
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality_metrics: Option<QualityMetrics>,  // Output compared with the source (video only, when requested)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crf_search: Option<CrfSearch>,  // CRF chosen for target_vmaf and the samples behind it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_sha256: Option<String>,  // Hex SHA-256 of the uploaded file (video and audio only)
+}
+
+/// Image conversion settings
+#[derive(Debug, Clone)]
+pub struct ImageConversionSettings {
+    /// Optional canvas padding applied before WebP encoding
+    pub pad: Option<PadSettings>,
+    /// Pixel filters applied in order before padding
+    pub filters: Vec<ImageFilter>,
+    /// WebP encoding mode
+    pub mode: WebpMode,
+    /// Lossy encoding quality (0-100)
+    pub quality: f32,
+    /// Minimum SSIM the lossy candidate must reach in auto mode
+    pub min_ssim: f64,
+    /// What to do when the WebP output is larger than the PNG input
+    pub size_policy: SizePolicy,
+    /// How embedded ICC profiles are handled
+    pub color_profile: ColorProfileMode,
+}
+
+impl Default for ImageConversionSettings {
+    fn default() -> Self {
+        Self {
+            pad: None,
+            filters: Vec::new(),
+            mode: WebpMode::Lossy,
+            quality: 85.0,
+            min_ssim: 0.98,
+            size_policy: SizePolicy::Always,
+            color_profile: ColorProfileMode::Srgb,
+        }
+    }
+}
+
+/// Handling of embedded ICC color profiles
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorProfileMode {
+    /// Convert pixels from the embedded profile to sRGB
+    #[default]
+    Srgb,
+    /// Keep the pixels as they are and embed the profile in the WebP output
+    Keep,
+    /// Drop the profile without converting the pixels
+    Ignore,
+}
+
+impl std::str::FromStr for ColorProfileMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "srgb" | "convert" => Ok(ColorProfileMode::Srgb),
+            "keep" => Ok(ColorProfileMode::Keep),
+            "ignore" | "strip" => Ok(ColorProfileMode::Ignore),
+            _ => Err(format!("Invalid color profile mode '{}', expected srgb, keep or ignore", s)),
+        }
+    }
+}
+
+impl ParamChoice for ColorProfileMode {
+    const ALLOWED: &'static [&'static str] = &["srgb", "keep", "ignore"];
+}
+
+/// WebP encoding modes
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WebpMode {
+    Lossy,
+    Lossless,
+    /// Encode both and keep the smaller result that meets the SSIM threshold
+    Auto,
+}
+
+impl std::str::FromStr for WebpMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "lossy" => Ok(WebpMode::Lossy),
+            "lossless" => Ok(WebpMode::Lossless),
+            "auto" => Ok(WebpMode::Auto),
+            _ => Err(format!("Invalid mode '{}', expected lossy, lossless or auto", s)),
+        }
+    }
+}
+
+impl ParamChoice for WebpMode {
+    const ALLOWED: &'static [&'static str] = &["lossy", "lossless", "auto"];
+}
+
+/// Policy applied when the converted output is larger than the input
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum SizePolicy {
+    /// Always return the converted output
+    #[default]
+    Always,
+    /// Return the original bytes unchanged when conversion does not save space
+    OnlyIfSmaller,
+    /// Fail the request when conversion does not save space
+    ErrorIfLarger,
+}
+
+impl std::str::FromStr for SizePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "always" => Ok(SizePolicy::Always),
+            "only-if-smaller" => Ok(SizePolicy::OnlyIfSmaller),
+            "error-if-larger" => Ok(SizePolicy::ErrorIfLarger),
+            _ => Err(format!("Invalid size policy '{}', expected always, only-if-smaller or error-if-larger", s)),
+        }
+    }
+}
+
+impl ParamChoice for SizePolicy {
+    const ALLOWED: &'static [&'static str] = &["always", "only-if-smaller", "error-if-larger"];
+}
+
+impl SizePolicy {
+    /// Decide whether the converted output should be returned
+    ///
+    /// Returns `Ok(false)` when the original should be returned instead, and an
+    /// error message when the policy rejects the result.
+    pub fn keep_converted(&self, original_size: usize, converted_size: usize) -> Result<bool, String> {
+        if converted_size < original_size {
+            return Ok(true);
+        }
+
+        match self {
+            SizePolicy::Always => Ok(true),
+            SizePolicy::OnlyIfSmaller => Ok(false),
+            SizePolicy::ErrorIfLarger => Err(format!(
+                "Converted output ({} bytes) is not smaller than the input ({} bytes)",
+                converted_size, original_size
+            )),
+        }
+    }
+}
+
+/// How a converted file is returned to the client
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseMode {
+    /// `ConvertResponse` JSON with the file base64 encoded
+    #[default]
+    Json,
+    /// The raw file as the body, with the metrics in `X-` headers
+    Binary,
+}
+
+impl std::str::FromStr for ResponseMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "json" => Ok(ResponseMode::Json),
+            "binary" => Ok(ResponseMode::Binary),
+            _ => Err(format!("Invalid response mode '{}', expected json or binary", s)),
+        }
+    }
+}
+
+impl ParamChoice for ResponseMode {
+    const ALLOWED: &'static [&'static str] = &["json", "binary"];
+}
+
+/// Query string of the endpoints that can return a binary body
+#[derive(Debug, Deserialize, Default)]
+pub struct ResponseQuery {
+    /// `json` or `binary`, parsed by the handler so errors match other parameters
+    pub response: Option<String>,
+}
+
+/// Result of the image conversion pipeline
+#[derive(Debug)]
+pub struct ImageConversionOutput {
+    pub webp_data: Vec<u8>,
+    /// Mode that produced `webp_data` (never `Auto`)
+    pub mode: WebpMode,
+    /// SSIM of the output against the processed source, computed in auto mode
+    pub ssim: Option<f64>,
+    /// Name of the ICC profile embedded in the source image
+    pub source_profile: Option<String>,
+}
+
+/// Pixel filters that can be applied to an image before encoding
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFilter {
+    /// Unsharp mask with blur sigma (0.1-10) and threshold (0-255)
+    Sharpen { sigma: f32, threshold: i32 },
+    /// Gaussian blur with sigma (0.1-50)
+    Blur { sigma: f32 },
+    /// Brightness offset (-255 to 255)
+    Brightness(i32),
+    /// Contrast adjustment in percent (-100 to 100)
+    Contrast(f32),
+    /// Gamma correction (0.1-10, 1.0 leaves the image unchanged)
+    Gamma(f32),
+    /// Saturation factor (0-5, 0 is grayscale, 1.0 leaves the image unchanged)
+    Saturation(f32),
+    Grayscale,
+    Sepia,
+    Invert,
+}
+
+impl ImageFilter {
+    /// Maximum number of filters accepted in a single request
+    pub const MAX_PER_REQUEST: usize = 16;
+
+    /// Parse a comma-separated filter chain, e.g. "sharpen:0.5:2,brightness:10,grayscale"
+    pub fn parse_list(s: &str) -> Result<Vec<ImageFilter>, String> {
+        let filters = s
+            .split(',')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<ImageFilter>, String>>()?;
+
+        if filters.len() > Self::MAX_PER_REQUEST {
+            return Err(format!("Too many filters ({}), at most {} are allowed",
+                               filters.len(), Self::MAX_PER_REQUEST));
+        }
+        Ok(filters)
+    }
+
+    /// Whether the filter alters colors rather than only the spatial detail
+    pub fn changes_color(&self) -> bool {
+        !matches!(self, ImageFilter::Sharpen { .. } | ImageFilter::Blur { .. })
+    }
+}
+
+/// Parse a numeric filter parameter and check it lies within the allowed range
+fn filter_param<T>(name: &str, value: Option<&str>, default: Option<T>, min: T, max: T) -> Result<T, String>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display + Copy,
+{
+    let value = match (value, default) {
+        (Some(v), _) => v
+            .trim()
+            .parse::<T>()
+            .map_err(|_| format!("Invalid {} value '{}'", name, v))?,
+        (None, Some(d)) => d,
+        (None, None) => return Err(format!("Filter '{}' requires a value", name)),
+    };
+
+    // Written as a negated range check so NaN is rejected too
+    if !(value >= min && value <= max) {
+        return Err(format!("{} must be between {} and {}, got {}", name, min, max, value));
+    }
+    Ok(value)
+}
+
+impl std::str::FromStr for ImageFilter {
+    type Err = String;
+
+    /// Parse "name" or "name:param[:param]"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_lowercase();
+        let mut parts = lower.split(':');
+        let name = parts.next().unwrap_or_default();
+        let first = parts.next();
+        let second = parts.next();
+
+        if parts.next().is_some() {
+            return Err(format!("Too many parameters for filter '{}'", name));
+        }
+
+        let filter = match name {
+            "sharpen" | "unsharp" => ImageFilter::Sharpen {
+                sigma: filter_param("sharpen sigma", first, Some(0.5), 0.1, 10.0)?,
+                threshold: filter_param("sharpen threshold", second, Some(1), 0, 255)?,
+            },
+            "blur" => ImageFilter::Blur {
+                sigma: filter_param("blur sigma", first, Some(1.0), 0.1, 50.0)?,
+            },
+            "brightness" => ImageFilter::Brightness(filter_param("brightness", first, None, -255, 255)?),
+            "contrast" => ImageFilter::Contrast(filter_param("contrast", first, None, -100.0, 100.0)?),
+            "gamma" => ImageFilter::Gamma(filter_param("gamma", first, None, 0.1, 10.0)?),
+            "saturation" => ImageFilter::Saturation(filter_param("saturation", first, None, 0.0, 5.0)?),
+            "grayscale" | "greyscale" => ImageFilter::Grayscale,
+            "sepia" => ImageFilter::Sepia,
+            "invert" => ImageFilter::Invert,
+            _ => {
+                return Err(format!(
+                    "Unknown filter '{}', expected one of: sharpen, blur, brightness, contrast, gamma, saturation, grayscale, sepia, invert",
+                    name
+                ))
+            }
+        };
+
+        // Parameterless filters and single-value filters must not carry extra parameters
+        let max_params = match filter {
+            ImageFilter::Sharpen { .. } => 2,
+            ImageFilter::Grayscale | ImageFilter::Sepia | ImageFilter::Invert => 0,
+            _ => 1,
+        };
+        let given = first.iter().chain(second.iter()).count();
+        if given > max_params {
+            return Err(format!("Filter '{}' accepts at most {} parameter(s)", name, max_params));
+        }
+
+        Ok(filter)
+    }
+}
+
+/// Canvas padding (letterbox) settings
+#[derive(Debug, Clone)]
+pub struct PadSettings {
+    pub target: PadTarget,
+    pub background: PadBackground,
+    pub gravity: Gravity,
+}
+
+/// Target canvas shape for padding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PadTarget {
+    /// Extend the canvas to the given aspect ratio (e.g. "1:1", "16:9")
+    Aspect { width: u32, height: u32 },
+    /// Fit the image inside an exact canvas size (e.g. "1200x1200")
+    Size { width: u32, height: u32 },
+}
+
+/// Fill used for the padded area
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PadBackground {
+    Transparent,
+    Color([u8; 4]),
+}
+
+impl PadBackground {
+    /// Whether the fill is fully or partly transparent, so the output needs an alpha channel
+    pub fn has_alpha(&self) -> bool {
+        match self {
+            PadBackground::Transparent => true,
+            PadBackground::Color([_, _, _, alpha]) => *alpha < 255,
+        }
+    }
+}
+
+/// Placement of the image inside the padded canvas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Gravity {
+    #[default]
+    Center,
+    North,
+    South,
+    East,
+    West,
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+}
+
+impl Default for PadBackground {
+    fn default() -> Self {
+        PadBackground::Color([255, 255, 255, 255])
+    }
+}
+
+impl std::str::FromStr for PadTarget {
+    type Err = String;
+
+    /// Parse "W:H" as an aspect ratio or "WxH" as an exact size
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let (target, (w, h)) = if let Some(parts) = s.split_once(':') {
+            ("aspect", parts)
+        } else if let Some(parts) = s.split_once('x') {
+            ("size", parts)
+        } else {
+            return Err(format!("Invalid pad target '{}', expected W:H or WxH", s));
+        };
+
+        let width: u32 = w.trim().parse().map_err(|_| format!("Invalid pad width '{}'", w))?;
+        let height: u32 = h.trim().parse().map_err(|_| format!("Invalid pad height '{}'", h))?;
+        if width == 0 || height == 0 {
+            return Err("Pad dimensions must be greater than zero".to_string());
+        }
+
+        Ok(match target {
+            "aspect" => PadTarget::Aspect { width, height },
+            _ => PadTarget::Size { width, height },
+        })
+    }
+}
+
+impl std::str::FromStr for PadBackground {
+    type Err = String;
+
+    /// Parse "transparent" or a hex color ("#RRGGBB" / "#RRGGBBAA")
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("transparent") {
+            return Ok(PadBackground::Transparent);
+        }
+
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid pad color '{}', expected #RRGGBB, #RRGGBBAA or transparent", s));
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(255);
+        let alpha = if hex.len() == 8 { channel(6) } else { 255 };
+        Ok(PadBackground::Color([channel(0), channel(2), channel(4), alpha]))
+    }
+}
+
+impl std::str::FromStr for Gravity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace(['-', '_'], "").as_str() {
+            "center" | "centre" => Ok(Gravity::Center),
+            "north" | "top" => Ok(Gravity::North),
+            "south" | "bottom" => Ok(Gravity::South),
+            "east" | "right" => Ok(Gravity::East),
+            "west" | "left" => Ok(Gravity::West),
+            "northeast" | "topright" => Ok(Gravity::NorthEast),
+            "northwest" | "topleft" => Ok(Gravity::NorthWest),
+            "southeast" | "bottomright" => Ok(Gravity::SouthEast),
+            "southwest" | "bottomleft" => Ok(Gravity::SouthWest),
+            _ => Err(format!("Invalid pad gravity '{}'", s)),
+        }
+    }
+}
+
+impl ParamChoice for Gravity {
+    const ALLOWED: &'static [&'static str] = &["center", "north", "south", "east", "west", "northeast", "northwest", "southeast", "southwest"];
+}
+
+impl Gravity {
+    /// Get the (x, y) offset that places content of the given size inside the canvas
+    pub fn offset(&self, canvas: (u32, u32), content: (u32, u32)) -> (u32, u32) {
+        let free_x = canvas.0.saturating_sub(content.0);
+        let free_y = canvas.1.saturating_sub(content.1);
+
+        let x = match self {
+            Gravity::West | Gravity::NorthWest | Gravity::SouthWest => 0,
+            Gravity::East | Gravity::NorthEast | Gravity::SouthEast => free_x,
+            _ => free_x / 2,
+        };
+        let y = match self {
+            Gravity::North | Gravity::NorthEast | Gravity::NorthWest => 0,
+            Gravity::South | Gravity::SouthEast | Gravity::SouthWest => free_y,
+            _ => free_y / 2,
+        };
+
+        (x, y)
+    }
+}
+
+/// Lifecycle state of a background conversion job
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// A client subscribed before the job started
+    Pending,
+    /// Waiting for a free worker, see `queue_position`
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    /// Stopped by a cancel request or because the client went away
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Whether no further updates will follow
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+/// Progress snapshot of a conversion job, published over Server-Sent Events
+#[derive(Debug, Serialize, Clone)]
+pub struct JobProgress {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub percent: Option<f64>,
+    pub out_time_secs: f64,
+    pub duration_secs: Option<f64>,
+    pub fps: Option<f64>,
+    pub speed: Option<f64>,
+    pub bitrate_kbps: Option<f64>,
+    pub eta_secs: Option<f64>,
+    /// 1-based position in the worker queue while queued
+    pub queue_position: Option<usize>,
+    pub message: Option<String>,
+}
+
+impl JobProgress {
+    pub fn new(job_id: String, status: JobStatus) -> Self {
+        Self {
+            job_id,
+            status,
+            percent: None,
+            out_time_secs: 0.0,
+            duration_secs: None,
+            fps: None,
+            speed: None,
+            bitrate_kbps: None,
+            eta_secs: None,
+            queue_position: None,
+            message: None,
+        }
+    }
+}
+
+/// Video input containers accepted by the video endpoint
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoContainer {
+    Mp4,
+    Mov,
+    Mkv,
+    WebM,
+    Avi,
+    Gif,
+}
+
+impl VideoContainer {
+    /// Human readable name used in logs and error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoContainer::Mp4 => "MP4",
+            VideoContainer::Mov => "QuickTime MOV",
+            VideoContainer::Mkv => "Matroska MKV",
+            VideoContainer::WebM => "WebM",
+            VideoContainer::Avi => "AVI",
+            VideoContainer::Gif => "GIF",
+        }
+    }
+
+    /// Usual file extension, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VideoContainer::Mp4 => "mp4",
+            VideoContainer::Mov => "mov",
+            VideoContainer::Mkv => "mkv",
+            VideoContainer::WebM => "webm",
+            VideoContainer::Avi => "avi",
+            VideoContainer::Gif => "gif",
+        }
+    }
+
+    /// MIME type used when the original file is returned
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            VideoContainer::Mp4 => "video/mp4",
+            VideoContainer::Mov => "video/quicktime",
+            VideoContainer::Mkv => "video/x-matroska",
+            VideoContainer::WebM => "video/webm",
+            VideoContainer::Avi => "video/x-msvideo",
+            VideoContainer::Gif => "image/gif",
+        }
+    }
+
+    /// Demuxer name ffprobe reports in `format_name` for this container
+    pub fn ffprobe_format(&self) -> &'static str {
+        match self {
+            VideoContainer::Mp4 | VideoContainer::Mov => "mov",
+            VideoContainer::Mkv | VideoContainer::WebM => "matroska",
+            VideoContainer::Avi => "avi",
+            VideoContainer::Gif => "gif",
+        }
+    }
+}
+
+/// Audio input containers accepted by the audio endpoint
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioContainer {
+    Wav,
+    Mp3,
+    Flac,
+}
+
+impl AudioContainer {
+    /// Human readable name used in logs and error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioContainer::Wav => "WAV",
+            AudioContainer::Mp3 => "MP3",
+            AudioContainer::Flac => "FLAC",
+        }
+    }
+
+    /// MIME type used when the original file is returned
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            AudioContainer::Wav => "audio/wav",
+            AudioContainer::Mp3 => "audio/mpeg",
+            AudioContainer::Flac => "audio/flac",
+        }
+    }
+
+    /// Demuxer name ffprobe reports in `format_name` for this container
+    pub fn ffprobe_format(&self) -> &'static str {
+        match self {
+            AudioContainer::Wav => "wav",
+            AudioContainer::Mp3 => "mp3",
+            AudioContainer::Flac => "flac",
+        }
+    }
 }
 
-/// Error response model
+/// Input of the audio pipeline: the audio track of a video, or a plain audio file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioSource {
+    Video(VideoContainer),
+    Audio(AudioContainer),
+}
+
+impl AudioSource {
+    /// Human readable name of the input container
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioSource::Video(container) => container.name(),
+            AudioSource::Audio(container) => container.name(),
+        }
+    }
+
+    /// MIME type of the input container
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            AudioSource::Video(container) => container.mime_type(),
+            AudioSource::Audio(container) => container.mime_type(),
+        }
+    }
+}
+
+/// Audio-only output formats
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// Opus in an Ogg container
+    #[default]
+    Opus,
+    /// Opus in an audio-only WebM container
+    WebM,
+    Mp3,
+    /// AAC in an M4A container
+    Aac,
+}
+
+impl std::str::FromStr for AudioFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "opus" | "ogg" => Ok(AudioFormat::Opus),
+            "webm" => Ok(AudioFormat::WebM),
+            "mp3" => Ok(AudioFormat::Mp3),
+            "aac" | "m4a" => Ok(AudioFormat::Aac),
+            _ => Err(format!("Invalid audio format '{}', expected opus, webm, mp3 or aac", s)),
+        }
+    }
+}
+
+impl ParamChoice for AudioFormat {
+    const ALLOWED: &'static [&'static str] = &["opus", "webm", "mp3", "aac"];
+}
+
+impl AudioFormat {
+    /// Human readable format name
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioFormat::Opus => "Ogg Opus",
+            AudioFormat::WebM => "WebM Opus",
+            AudioFormat::Mp3 => "MP3",
+            AudioFormat::Aac => "AAC",
+        }
+    }
+
+    /// File extension, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Opus => "opus",
+            AudioFormat::WebM => "webm",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Aac => "m4a",
+        }
+    }
+
+    /// MIME type of the output file
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            AudioFormat::Opus => "audio/ogg",
+            AudioFormat::WebM => "audio/webm",
+            AudioFormat::Mp3 => "audio/mpeg",
+            AudioFormat::Aac => "audio/mp4",
+        }
+    }
+
+    /// ffmpeg muxer name
+    pub fn muxer(&self) -> &'static str {
+        match self {
+            AudioFormat::Opus => "ogg",
+            AudioFormat::WebM => "webm",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Aac => "ipod",
+        }
+    }
+
+    /// ffmpeg audio encoder name
+    pub fn encoder(&self) -> &'static str {
+        match self {
+            AudioFormat::Opus | AudioFormat::WebM => "libopus",
+            AudioFormat::Mp3 => "libmp3lame",
+            AudioFormat::Aac => "aac",
+        }
+    }
+
+    /// Default bitrate in kbit/s
+    pub fn default_bitrate_kbps(&self) -> u32 {
+        match self {
+            AudioFormat::Opus | AudioFormat::WebM => 64,
+            AudioFormat::Mp3 | AudioFormat::Aac => 128,
+        }
+    }
+
+    /// Accepted bitrate range in kbit/s
+    pub fn bitrate_range(&self) -> (u32, u32) {
+        match self {
+            AudioFormat::Opus | AudioFormat::WebM => (6, 510),
+            AudioFormat::Mp3 => (8, 320),
+            AudioFormat::Aac => (16, 512),
+        }
+    }
+
+    /// Output sample rates the encoder supports
+    pub fn sample_rates(&self) -> &'static [u32] {
+        match self {
+            AudioFormat::Opus | AudioFormat::WebM => &[8000, 12000, 16000, 24000, 48000],
+            AudioFormat::Mp3 => &[8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000],
+            AudioFormat::Aac => &[8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000],
+        }
+    }
+}
+
+/// Settings of the audio-only pipeline
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AudioSettings {
+    pub format: AudioFormat,
+    /// Bitrate in kbit/s, the format default when `None`
+    pub bitrate_kbps: Option<u32>,
+    /// Output channels (1 or 2), the source layout when `None`
+    pub channels: Option<u32>,
+    /// Output sample rate in Hz, the source rate when `None`
+    pub sample_rate: Option<u32>,
+    /// Opus application mode, libopus' default (`audio`) when `None`
+    pub application: Option<OpusApplication>,
+    pub loudnorm: Option<Loudnorm>,
+    pub size_policy: SizePolicy,
+}
+
+impl AudioSettings {
+    /// Check bitrate, channels and sample rate against the output format
+    pub fn validate(&self) -> Result<(), ParamError> {
+        let (min, max) = self.format.bitrate_range();
+        if let Some(kbps) = self.bitrate_kbps.filter(|kbps| !(min..=max).contains(kbps)) {
+            let message = format!("{} bitrate must be between {}k and {}k, got {}k", self.format.name(), min, max, kbps);
+            return Err(ParamError::range("audio_bitrate", message, format!("{}k", min), format!("{}k", max)));
+        }
+        if let Some(channels) = self.channels.filter(|channels| !(1..=2).contains(channels)) {
+            let message = format!("Audio channels must be 1 or 2, got {}", channels);
+            return Err(ParamError::one_of("audio_channels", message, &["1", "2"]));
+        }
+        if self.application.is_some() && !matches!(self.format, AudioFormat::Opus | AudioFormat::WebM) {
+            let message = format!("opus_application does not apply to {} output", self.format.name());
+            return Err(ParamError::one_of("audio_format", message, &["opus", "webm"]));
+        }
+        let rates = self.format.sample_rates();
+        if let Some(rate) = self.sample_rate.filter(|rate| !rates.contains(rate)) {
+            let rates: Vec<String> = rates.iter().map(|r| r.to_string()).collect();
+            let message = format!(
+                "{} does not support a {} Hz sample rate, expected one of {}",
+                self.format.name(),
+                rate,
+                rates.join(", ")
+            );
+            let rates: Vec<&str> = rates.iter().map(String::as_str).collect();
+            return Err(ParamError::one_of("audio_sample_rate", message, &rates));
+        }
+        Ok(())
+    }
+}
+
+/// Audio options of the video pipeline
+#[derive(Debug, Deserialize, Clone)]
+pub struct VideoAudioSettings {
+    /// Drop the audio track entirely
+    pub mute: bool,
+    /// Opus bitrate in kbit/s
+    pub bitrate_kbps: u32,
+    pub application: OpusApplication,
+    /// Output channels (1 or 2), the source layout when `None`
+    pub channels: Option<u32>,
+    /// Output sample rate in Hz, 48 kHz when `None`
+    pub sample_rate: Option<u32>,
+    pub loudnorm: Option<Loudnorm>,
+}
+
+impl Default for VideoAudioSettings {
+    fn default() -> Self {
+        Self {
+            mute: false,
+            bitrate_kbps: AudioFormat::WebM.default_bitrate_kbps(),
+            application: OpusApplication::default(),
+            channels: None,
+            sample_rate: None,
+            loudnorm: None,
+        }
+    }
+}
+
+impl VideoAudioSettings {
+    /// Check bitrate, channels and sample rate against what libopus supports
+    pub fn validate(&self) -> Result<(), ParamError> {
+        AudioSettings {
+            format: AudioFormat::WebM,
+            bitrate_kbps: Some(self.bitrate_kbps),
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            ..AudioSettings::default()
+        }
+        .validate()
+    }
+}
+
+/// libopus application modes
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OpusApplication {
+    /// Tuned for speech intelligibility
+    #[default]
+    Voip,
+    /// Tuned for music and mixed content
+    Audio,
+    /// Lowest latency, lower quality
+    LowDelay,
+}
+
+impl std::str::FromStr for OpusApplication {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "voip" => Ok(OpusApplication::Voip),
+            "audio" => Ok(OpusApplication::Audio),
+            "lowdelay" => Ok(OpusApplication::LowDelay),
+            _ => Err(format!("Invalid Opus application '{}', expected voip, audio or lowdelay", s)),
+        }
+    }
+}
+
+impl ParamChoice for OpusApplication {
+    const ALLOWED: &'static [&'static str] = &["voip", "audio", "lowdelay"];
+}
+
+impl OpusApplication {
+    /// Value of libopus' `-application` option
+    pub fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            OpusApplication::Voip => "voip",
+            OpusApplication::Audio => "audio",
+            OpusApplication::LowDelay => "lowdelay",
+        }
+    }
+}
+
+/// EBU R128 loudness normalization targets for ffmpeg's `loudnorm` filter
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub struct Loudnorm {
+    /// Integrated loudness target in LUFS (-70 to -5)
+    pub integrated: f64,
+    /// Maximum true peak in dBTP (-9 to 0)
+    pub true_peak: f64,
+    /// Loudness range target in LU (1 to 50)
+    pub range: f64,
+}
+
+impl Default for Loudnorm {
+    /// The EBU R128 broadcast target
+    fn default() -> Self {
+        Self { integrated: -23.0, true_peak: -1.0, range: 7.0 }
+    }
+}
+
+impl std::str::FromStr for Loudnorm {
+    type Err = String;
+
+    /// Parse `I[:TP[:LRA]]`, e.g. `-16:-1.5:11`, with the EBU R128 target for omitted values
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut loudnorm = Loudnorm::default();
+        let parts: Vec<&str> = s.trim().split(':').collect();
+        if parts.len() > 3 {
+            return Err(format!("Invalid loudnorm '{}', expected I[:TP[:LRA]]", s));
+        }
+
+        let targets = [
+            ("integrated loudness", -70.0, -5.0),
+            ("true peak", -9.0, 0.0),
+            ("loudness range", 1.0, 50.0),
+        ];
+        for (i, (part, (name, min, max))) in parts.iter().zip(targets).enumerate() {
+            let value = part
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid loudnorm {} '{}'", name, part.trim()))?;
+            if !(value >= min && value <= max) {
+                return Err(format!("Loudnorm {} must be between {} and {}", name, min, max));
+            }
+            match i {
+                0 => loudnorm.integrated = value,
+                1 => loudnorm.true_peak = value,
+                _ => loudnorm.range = value,
+            }
+        }
+        Ok(loudnorm)
+    }
+}
+
+impl Loudnorm {
+    /// Filter for the measurement pass, printing the input statistics as JSON
+    pub fn measure_filter(&self) -> String {
+        format!("{}:print_format=json", self.targets())
+    }
+
+    /// Filter for the normalization pass
+    ///
+    /// With a measurement the gain is applied linearly; without one loudnorm
+    /// falls back to single-pass dynamic normalization.
+    pub fn apply_filter(&self, measurement: Option<&LoudnessMeasurement>) -> String {
+        match measurement {
+            Some(m) => format!(
+                "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+                self.targets(),
+                m.input_i,
+                m.input_tp,
+                m.input_lra,
+                m.input_thresh,
+                m.target_offset
+            ),
+            None => self.targets(),
+        }
+    }
+
+    fn targets(&self) -> String {
+        format!("loudnorm=I={}:TP={}:LRA={}", self.integrated, self.true_peak, self.range)
+    }
+}
+
+/// Input statistics printed by the `loudnorm` measurement pass
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub struct LoudnessMeasurement {
+    #[serde(deserialize_with = "f64_from_str")]
+    pub input_i: f64,
+    #[serde(deserialize_with = "f64_from_str")]
+    pub input_tp: f64,
+    #[serde(deserialize_with = "f64_from_str")]
+    pub input_lra: f64,
+    #[serde(deserialize_with = "f64_from_str")]
+    pub input_thresh: f64,
+    #[serde(deserialize_with = "f64_from_str")]
+    pub target_offset: f64,
+}
+
+/// ffmpeg prints loudnorm statistics as strings, and "-inf" for silence
+fn f64_from_str<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| serde::de::Error::custom(format!("not a finite number: {}", value)))
+}
+
+/// Result of the audio conversion pipeline
+#[derive(Debug)]
+pub struct AudioConversionOutput {
+    /// Converted file, deleted when dropped
+    pub audio_file: tempfile::TempPath,
+    pub audio_size: u64,
+    pub bitrate_kbps: u32,
+}
+
+/// Result of the video conversion pipeline
+#[derive(Debug)]
+pub struct VideoConversionOutput {
+    /// Converted file, deleted when dropped
+    pub webm_file: tempfile::TempPath,
+    pub webm_size: u64,
+    /// ffmpeg video encoder that produced `webm_file`
+    pub video_encoder: VideoEncoder,
+    /// Average video bitrate requested from the encoder in bitrate or target-size mode
+    pub video_bitrate_kbps: Option<u32>,
+    /// WebP poster frame of the output, when requested
+    pub poster_webp: Option<Vec<u8>>,
+    /// VMAF, SSIM and PSNR of the output against the source, when requested
+    pub quality_metrics: Option<QualityMetrics>,
+    /// CRF search behind a `target_vmaf` encode
+    pub crf_search: Option<CrfSearch>,
+}
+
+/// Subset of `ffprobe -of json` output used by the video pipeline
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ProbeOutput {
+    #[serde(default)]
+    pub streams: Vec<ProbeStream>,
+    pub format: Option<ProbeFormat>,
+}
+
+/// Stream entry of ffprobe output
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ProbeStream {
+    pub index: Option<u32>,
+    pub codec_type: Option<String>,
+    pub codec_name: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub avg_frame_rate: Option<String>,
+    pub r_frame_rate: Option<String>,
+    pub bit_rate: Option<String>,
+    pub channels: Option<u32>,
+    pub sample_rate: Option<String>,
+    #[serde(default)]
+    pub tags: ProbeTags,
+    #[serde(default)]
+    pub side_data_list: Vec<ProbeSideData>,
+    #[serde(default)]
+    pub disposition: ProbeDisposition,
+}
+
+/// Stream tags of ffprobe output
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ProbeTags {
+    /// Rotation written by older muxers, in degrees
+    pub rotate: Option<String>,
+}
+
+/// Stream side data of ffprobe output
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ProbeSideData {
+    /// Rotation of a display matrix, in degrees
+    pub rotation: Option<f64>,
+}
+
+/// Stream disposition flags of ffprobe output
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ProbeDisposition {
+    /// Set on cover art stored as a single-frame video stream
+    #[serde(default)]
+    pub attached_pic: u8,
+}
+
+impl ProbeStream {
+    /// Whether the stream is cover art rather than actual video
+    pub fn is_attached_picture(&self) -> bool {
+        self.disposition.attached_pic != 0
+    }
+
+    /// Average frame rate, parsed from ffprobe's "num/den" notation
+    ///
+    /// Falls back to the base frame rate for streams without an average.
+    pub fn frame_rate(&self) -> Option<f64> {
+        parse_rational(self.avg_frame_rate.as_deref()).or_else(|| parse_rational(self.r_frame_rate.as_deref()))
+    }
+
+    /// Stream bitrate in kbit/s
+    pub fn bitrate_kbps(&self) -> Option<u32> {
+        self.bit_rate.as_deref()?.parse::<u64>().ok().map(|bps| (bps / 1000) as u32)
+    }
+
+    /// Audio sample rate in Hz
+    pub fn sample_rate_hz(&self) -> Option<u32> {
+        self.sample_rate.as_deref()?.parse().ok()
+    }
+
+    /// Display rotation in degrees clockwise, normalised to 0-359
+    pub fn rotation(&self) -> Option<u32> {
+        // Display matrices rotate counter-clockwise, the legacy tag clockwise
+        let degrees = self
+            .side_data_list
+            .iter()
+            .find_map(|side_data| side_data.rotation)
+            .map(|rotation| -rotation)
+            .or_else(|| self.tags.rotate.as_deref()?.parse::<f64>().ok())?;
+        Some((degrees.round() as i64).rem_euclid(360) as u32)
+    }
+}
+
+/// Parse an ffprobe "num/den" rational, ignoring the "0/0" placeholder
+fn parse_rational(value: Option<&str>) -> Option<f64> {
+    let (num, den) = value?.split_once('/')?;
+    let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+    Some(num / den).filter(|fps| fps.is_finite() && *fps > 0.0)
+}
+
+/// Format (container) entry of ffprobe output
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ProbeFormat {
+    pub format_name: Option<String>,
+    pub duration: Option<String>,
+    pub bit_rate: Option<String>,
+}
+
+/// An external tool and whether it could be run
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ToolInfo {
+    pub path: String,
+    pub available: bool,
+    /// Version reported by `-version`, e.g. "6.1.1"
+    pub version: Option<String>,
+}
+
+/// What the configured ffmpeg build can do for this service, detected at startup
+///
+/// The lists only hold the components the service uses.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct FfmpegCapabilities {
+    pub ffmpeg: ToolInfo,
+    pub ffprobe: ToolInfo,
+    pub encoders: Vec<String>,
+    pub decoders: Vec<String>,
+    pub filters: Vec<String>,
+}
+
+impl FfmpegCapabilities {
+    /// Encoders the service can use
+    pub const ENCODERS: &'static [&'static str] = &[
+        "libvpx-vp9", "libvpx", "libsvtav1", "libaom-av1", "libwebp", "gif", "png",
+        "libopus", "libmp3lame", "aac",
+    ];
+    /// Decoders of the accepted input codecs
+    pub const DECODERS: &'static [&'static str] = &[
+        "h264", "hevc", "mpeg4", "mpeg2video", "mjpeg", "prores", "vp8", "libvpx", "vp9", "libvpx-vp9",
+        "av1", "libdav1d", "libaom-av1", "gif",
+        "aac", "mp3", "mp3float", "flac", "alac", "opus", "vorbis", "ac3", "ac3_fixed", "eac3",
+        "pcm_s16le", "pcm_s16be", "pcm_s24le", "pcm_s32le", "pcm_f32le", "pcm_u8",
+    ];
+    /// Filters used by the video and audio pipelines
+    pub const FILTERS: &'static [&'static str] = &[
+        "scale", "fps", "split", "palettegen", "paletteuse", "thumbnail", "concat", "trim", "atrim",
+        "setpts", "asetpts", "loudnorm", "format", "scale2ref", "ssim", "psnr", "libvmaf",
+    ];
+
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.iter().any(|e| e == name)
+    }
+
+    pub fn has_filter(&self, name: &str) -> bool {
+        self.filters.iter().any(|f| f == name)
+    }
+
+    /// Whether this build can decode a codec as named by ffprobe
+    ///
+    /// Codecs without a known decoder, and builds whose decoders could not be
+    /// listed, are assumed decodable and left to ffmpeg to report.
+    pub fn can_decode(&self, codec: &str) -> bool {
+        let names: &[&str] = match codec {
+            "av1" => &["av1", "libdav1d", "libaom-av1"],
+            "vp9" => &["vp9", "libvpx-vp9"],
+            "vp8" => &["vp8", "libvpx"],
+            "mp3" => &["mp3float", "mp3"],
+            "ac3" => &["ac3", "ac3_fixed"],
+            _ => std::slice::from_ref(&codec),
+        };
+        if self.decoders.is_empty() || !names.iter().any(|name| Self::DECODERS.contains(name)) {
+            return true;
+        }
+        names.iter().any(|name| self.decoders.iter().any(|d| d == name))
+    }
+
+    /// Output features and whether this ffmpeg build supports them
+    pub fn features(&self) -> Vec<(&'static str, bool)> {
+        let any = |names: &[&str]| names.iter().any(|name| self.has_encoder(name));
+        let available = self.ffmpeg.available;
+        vec![
+            ("webm_vp9", available && any(&["libvpx-vp9"])),
+            ("webm_av1", available && any(&["libsvtav1", "libaom-av1"])),
+            ("webm_vp8", available && any(&["libvpx"])),
+            ("animated_webp", available && any(&["libwebp"])),
+            ("gif", available && any(&["gif"]) && self.has_filter("palettegen")),
+            ("opus", available && any(&["libopus"])),
+            ("mp3", available && any(&["libmp3lame"])),
+            ("aac", available && any(&["aac"])),
+            ("loudnorm", available && self.has_filter("loudnorm")),
+            ("quality_metrics", available && ["scale2ref", "ssim", "psnr"].iter().all(|f| self.has_filter(f))),
+            ("vmaf", available && self.has_filter("libvmaf")),
+            ("poster", available && any(&["png"])),
+            ("inspect", self.ffprobe.available),
+        ]
+    }
+}
+
+/// Response of the video inspect endpoint
 #[derive(Debug, Serialize)]
-pub struct ApiError {
+pub struct InspectResponse {
     pub success: bool,
-    pub error: String,
-    pub code: String,
-    pub timestamp: String,
+    pub filename: Option<String>,
+    pub size: usize,
+    pub sha256: String,  // Hex SHA-256 of the uploaded file
+    pub container: String,  // Container detected from the file's magic bytes
+    pub format_name: Option<String>,  // ffprobe demuxer name
+    pub duration_secs: Option<f64>,
+    pub bitrate_kbps: Option<u32>,
+    pub streams: Vec<StreamInfo>,
+    pub estimates: Vec<SizeEstimate>,  // Estimated WebM size per quality level
+    pub job_id: String,  // Job id used for queue position reporting
 }
 
-/// Request model for image conversion (if using JSON instead of multipart)
-#[derive(Debug, Deserialize)]
-pub struct ConvertRequest {
-    pub image_data: String,  // Base64 encoded PNG data
-    pub filename: Option<String>,
+/// Summary of one stream of an inspected file
+#[derive(Debug, Serialize)]
+pub struct StreamInfo {
+    pub index: Option<u32>,
+    pub codec_type: Option<String>,
+    pub codec_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitrate_kbps: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+}
+
+impl From<&ProbeStream> for StreamInfo {
+    fn from(stream: &ProbeStream) -> Self {
+        let video = stream.codec_type.as_deref() == Some("video");
+        StreamInfo {
+            index: stream.index,
+            codec_type: stream.codec_type.clone(),
+            codec_name: stream.codec_name.clone(),
+            width: stream.width,
+            height: stream.height,
+            frame_rate: video.then(|| stream.frame_rate()).flatten(),
+            rotation: video.then(|| stream.rotation()).flatten(),
+            bitrate_kbps: stream.bitrate_kbps(),
+            channels: stream.channels,
+            sample_rate: stream.sample_rate_hz(),
+        }
+    }
+}
+
+/// Estimated WebM output size for one quality level
+#[derive(Debug, Serialize)]
+pub struct SizeEstimate {
+    pub quality: CompressionQuality,
+    pub estimated_size: u64,
+    pub reduction_percent: f64,
+}
+
+impl ProbeOutput {
+    /// Overall container bitrate in kbit/s
+    pub fn bitrate_kbps(&self) -> Option<u32> {
+        let bps = self.format.as_ref()?.bit_rate.as_deref()?.parse::<u64>().ok()?;
+        Some((bps / 1000) as u32)
+    }
+
+    /// Container duration in seconds, if known
+    pub fn duration_secs(&self) -> Option<f64> {
+        self.format
+            .as_ref()
+            .and_then(|f| f.duration.as_deref())
+            .and_then(|d| d.parse::<f64>().ok())
+            .filter(|d| d.is_finite() && *d > 0.0)
+    }
+
+    /// Streams of the given type ("video", "audio", ...), skipping attached cover art
+    pub fn streams_of(&self, codec_type: &str) -> impl Iterator<Item = &ProbeStream> {
+        let codec_type = codec_type.to_string();
+        self.streams
+            .iter()
+            .filter(move |s| s.codec_type.as_deref() == Some(codec_type.as_str()) && !s.is_attached_picture())
+    }
 }
 
 /// Video compression quality settings
 #[derive(Debug, Deserialize, Clone)]
 pub struct VideoCompressionSettings {
     pub quality: CompressionQuality,
-    pub audio_bitrate: Option<String>,
+    pub video_codec: VideoCodec,
+    pub rate_control: RateControl,
+    pub audio: VideoAudioSettings,
+    pub size_policy: SizePolicy,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub fps: Option<f64>,
+    pub scale_algorithm: ScaleAlgorithm,
+    pub segments: Vec<TrimSegment>,
+    pub poster: Option<PosterFrame>,
+    pub output_format: VideoOutputFormat,
+    /// Times an animation plays, 0 loops forever
+    pub loop_count: u32,
+    /// Palette size for GIF output
+    pub palette_colors: u16,
+    pub dither: Dither,
+    /// Score the output against the source after encoding
+    pub quality_metrics: bool,
+}
+
+impl VideoCompressionSettings {
+    /// Largest accepted value for `max_width`/`max_height`
+    pub const MAX_DIMENSION: u32 = 8192;
+    /// Highest accepted output frame rate
+    pub const MAX_FPS: f64 = 120.0;
+    /// Frame rate cap for animations when `fps` is not set
+    pub const ANIMATION_FPS: f64 = 15.0;
+    /// Longest accepted animated output, in seconds
+    pub const MAX_ANIMATION_SECS: f64 = 60.0;
+
+    /// Build the `-vf` filter chain for the resizing, frame-rate and palette options
+    ///
+    /// Videos are only ever scaled down, keeping the aspect ratio and even
+    /// dimensions as required by 4:2:0 encoding. The frame rate is only capped
+    /// when the source is faster than requested, so frames are never duplicated.
+    /// GIF output gets a palette generated from the clip itself.
+    pub fn video_filter(&self, source_fps: Option<f64>) -> Option<String> {
+        let mut filters = Vec::new();
+
+        let flags = self.scale_algorithm.ffmpeg_name();
+        match (self.max_width, self.max_height) {
+            (Some(width), Some(height)) => filters.push(format!(
+                "scale=w='min(iw,{})':h='min(ih,{})':force_original_aspect_ratio=decrease:force_divisible_by=2:flags={}",
+                width, height, flags
+            )),
+            // -2 only evens out the derived side, so round the capped side down to even as well
+            (Some(width), None) => filters.push(format!("scale=w='trunc(min(iw,{})/2)*2':h=-2:flags={}", width, flags)),
+            (None, Some(height)) => filters.push(format!("scale=w=-2:h='trunc(min(ih,{})/2)*2':flags={}", height, flags)),
+            (None, None) => {}
+        }
+
+        filters.extend(self.fps_filter(source_fps));
+
+        if self.output_format == VideoOutputFormat::Gif {
+            filters.push(format!(
+                "split[s0][s1];[s0]palettegen=max_colors={}:stats_mode=diff[p];[s1][p]paletteuse=dither={}:diff_mode=rectangle",
+                self.palette_colors,
+                self.dither.ffmpeg_name()
+            ));
+        }
+
+        (!filters.is_empty()).then(|| filters.join(","))
+    }
+
+    /// The `fps` filter of `video_filter`, if the frame rate gets capped
+    ///
+    /// Quality scoring applies it to the source so its frames line up with the output.
+    pub fn fps_filter(&self, source_fps: Option<f64>) -> Option<String> {
+        let animation = self.output_format.is_animation();
+        let fps = self.fps.or(animation.then_some(Self::ANIMATION_FPS))?;
+        source_fps.is_none_or(|source| source > fps).then(|| format!("fps={}", fps))
+    }
+}
+
+/// Distribution of a per-frame quality score
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ScoreSummary {
+    pub mean: f64,
+    pub min: f64,
+    /// 1st and 5th percentiles, the worst frames ignoring single outliers
+    pub p1: f64,
+    pub p5: f64,
+    pub median: f64,
+}
+
+impl ScoreSummary {
+    /// Summarize per-frame scores, or `None` when there are none
+    pub fn from_scores(mut scores: Vec<f64>) -> Option<Self> {
+        scores.retain(|score| !score.is_nan());
+        if scores.is_empty() {
+            return None;
+        }
+        scores.sort_by(f64::total_cmp);
+
+        // Nearest-rank percentile
+        let percentile = |p: f64| {
+            let rank = ((p / 100.0) * scores.len() as f64).ceil() as usize;
+            scores[rank.clamp(1, scores.len()) - 1]
+        };
+        let round = |value: f64| (value * 10_000.0).round() / 10_000.0;
+        Some(Self {
+            mean: round(scores.iter().sum::<f64>() / scores.len() as f64),
+            min: round(scores[0]),
+            p1: round(percentile(1.0)),
+            p5: round(percentile(5.0)),
+            median: round(percentile(50.0)),
+        })
+    }
+}
+
+/// Quality of an encoded video compared with its source
+#[derive(Debug, Serialize, Clone)]
+pub struct QualityMetrics {
+    /// Frames compared
+    pub frames: usize,
+    /// VMAF (0-100), absent when ffmpeg is built without libvmaf
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vmaf: Option<ScoreSummary>,
+    /// SSIM over all planes (0-1)
+    pub ssim: Option<ScoreSummary>,
+    /// PSNR over all planes in dB, capped at 100 for identical frames
+    pub psnr: Option<ScoreSummary>,
+}
+
+/// Output formats of the video endpoint
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoOutputFormat {
+    #[default]
+    WebM,
+    /// Animated WebP, looping and without audio
+    WebP,
+    /// Animated GIF with a generated palette
+    Gif,
+}
+
+impl std::str::FromStr for VideoOutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "webm" => Ok(VideoOutputFormat::WebM),
+            "webp" => Ok(VideoOutputFormat::WebP),
+            "gif" => Ok(VideoOutputFormat::Gif),
+            _ => Err(format!("Invalid output format '{}', expected webm, webp or gif", s)),
+        }
+    }
+}
+
+impl ParamChoice for VideoOutputFormat {
+    const ALLOWED: &'static [&'static str] = &["webm", "webp", "gif"];
+}
+
+impl VideoOutputFormat {
+    /// Human readable format name
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoOutputFormat::WebM => "WebM",
+            VideoOutputFormat::WebP => "animated WebP",
+            VideoOutputFormat::Gif => "GIF",
+        }
+    }
+
+    /// File extension, also the ffmpeg muxer name
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VideoOutputFormat::WebM => "webm",
+            VideoOutputFormat::WebP => "webp",
+            VideoOutputFormat::Gif => "gif",
+        }
+    }
+
+    /// MIME type of the output file
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            VideoOutputFormat::WebM => "video/webm",
+            VideoOutputFormat::WebP => "image/webp",
+            VideoOutputFormat::Gif => "image/gif",
+        }
+    }
+
+    /// Whether the output is a silent looping animation
+    pub fn is_animation(&self) -> bool {
+        !matches!(self, VideoOutputFormat::WebM)
+    }
+
+    /// Candidate encoders in order of preference
+    pub fn encoders(&self, codec: VideoCodec) -> &'static [VideoEncoder] {
+        match self {
+            VideoOutputFormat::WebM => codec.encoders(),
+            VideoOutputFormat::WebP => &[VideoEncoder::LibWebp],
+            VideoOutputFormat::Gif => &[VideoEncoder::Gif],
+        }
+    }
+}
+
+/// Dithering used when mapping GIF frames to the palette
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Dither {
+    /// Error diffusion with little noise, ffmpeg's default
+    #[default]
+    Sierra2_4a,
+    /// Ordered pattern, compresses best
+    Bayer,
+    FloydSteinberg,
+    /// No dithering, flat colour bands
+    None,
+}
+
+impl std::str::FromStr for Dither {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "sierra2_4a" => Ok(Dither::Sierra2_4a),
+            "bayer" => Ok(Dither::Bayer),
+            "floyd_steinberg" => Ok(Dither::FloydSteinberg),
+            "none" => Ok(Dither::None),
+            _ => Err(format!(
+                "Invalid dither '{}', expected sierra2_4a, bayer, floyd_steinberg or none",
+                s
+            )),
+        }
+    }
+}
+
+impl ParamChoice for Dither {
+    const ALLOWED: &'static [&'static str] = &["sierra2_4a", "bayer", "floyd_steinberg", "none"];
+}
+
+impl Dither {
+    /// Name of the mode in ffmpeg's `paletteuse` filter
+    pub fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            Dither::Sierra2_4a => "sierra2_4a",
+            Dither::Bayer => "bayer",
+            Dither::FloydSteinberg => "floyd_steinberg",
+            Dither::None => "none",
+        }
+    }
+}
+
+/// Which frame of a video to use as its poster image
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PosterFrame {
+    /// Most representative frame near the start, skipping black and fade frames
+    Auto,
+    /// Frame at the given timestamp in seconds
+    At(f64),
+}
+
+impl std::str::FromStr for PosterFrame {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" | "true" => Ok(PosterFrame::Auto),
+            other => parse_timestamp(other)
+                .map(PosterFrame::At)
+                .map_err(|_| format!("Invalid poster frame '{}', expected auto or a timestamp", s)),
+        }
+    }
+}
+
+/// Time range of the input to keep, in seconds
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub struct TrimSegment {
+    pub start: f64,
+    /// End of the range, or the end of the input when `None`
+    pub end: Option<f64>,
+}
+
+impl TrimSegment {
+    /// Maximum number of segments in one request
+    pub const MAX_SEGMENTS: usize = 20;
+
+    /// Create a segment, checking that it is not empty
+    pub fn new(start: f64, end: Option<f64>) -> Result<Self, String> {
+        if let Some(end) = end {
+            if end <= start {
+                return Err(format!("Segment end {}s must be after its start {}s", end, start));
+            }
+        }
+        Ok(TrimSegment { start, end })
+    }
+
+    /// Parse a comma separated list of `start-end` ranges, e.g. `0:05-0:12,1:30-1:45`
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, String> {
+        let segments = s
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                let (start, end) = part
+                    .split_once('-')
+                    .ok_or_else(|| format!("Invalid segment '{}', expected start-end", part))?;
+                TrimSegment::new(parse_timestamp(start)?, Some(parse_timestamp(end)?))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if segments.is_empty() {
+            return Err("segments must contain at least one start-end range".to_string());
+        }
+        if segments.len() > Self::MAX_SEGMENTS {
+            return Err(format!("Too many segments ({}), at most {} are allowed", segments.len(), Self::MAX_SEGMENTS));
+        }
+        Ok(segments)
+    }
+
+    /// Length of the segment, given the input duration for open-ended segments
+    pub fn length(&self, input_duration: Option<f64>) -> Option<f64> {
+        self.end.or(input_duration).map(|end| end - self.start)
+    }
+}
+
+/// Parse a timestamp given as seconds (`12.5`), `MM:SS` or `HH:MM:SS(.fff)`
+pub fn parse_timestamp(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let invalid = || format!("Invalid timestamp '{}', expected seconds, MM:SS or HH:MM:SS", s);
+
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+
+    let (last, whole) = parts.split_last().ok_or_else(invalid)?;
+    let mut seconds = last.parse::<f64>().map_err(|_| invalid())?;
+    if !(seconds >= 0.0 && seconds.is_finite()) || (!whole.is_empty() && seconds >= 60.0) {
+        return Err(invalid());
+    }
+    for (unit, part) in whole.iter().rev().enumerate() {
+        let value = part.parse::<u32>().map_err(|_| invalid())?;
+        if unit == 0 && parts.len() == 3 && value >= 60 {
+            return Err(invalid());
+        }
+        seconds += value as f64 * 60f64.powi(unit as i32 + 1);
+    }
+    Ok(seconds)
+}
+
+/// Scaling algorithms for resized video
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleAlgorithm {
+    /// Sharp and reasonably fast, ffmpeg's default
+    #[default]
+    Bicubic,
+    /// Fastest smooth scaling
+    Bilinear,
+    /// Sharpest result, best for text in screen recordings
+    Lanczos,
+    /// Averages source pixels, good for large downscales
+    Area,
+    /// Nearest neighbour, keeps pixel art crisp
+    Neighbor,
+}
+
+impl std::str::FromStr for ScaleAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bicubic" => Ok(ScaleAlgorithm::Bicubic),
+            "bilinear" => Ok(ScaleAlgorithm::Bilinear),
+            "lanczos" => Ok(ScaleAlgorithm::Lanczos),
+            "area" => Ok(ScaleAlgorithm::Area),
+            "neighbor" | "nearest" => Ok(ScaleAlgorithm::Neighbor),
+            _ => Err(format!(
+                "Invalid scale algorithm '{}', expected bicubic, bilinear, lanczos, area or neighbor",
+                s
+            )),
+        }
+    }
+}
+
+impl ParamChoice for ScaleAlgorithm {
+    const ALLOWED: &'static [&'static str] = &["bicubic", "bilinear", "lanczos", "area", "neighbor"];
+}
+
+impl ScaleAlgorithm {
+    /// Name of the algorithm in ffmpeg's `flags` option
+    pub fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            ScaleAlgorithm::Bicubic => "bicubic",
+            ScaleAlgorithm::Bilinear => "bilinear",
+            ScaleAlgorithm::Lanczos => "lanczos",
+            ScaleAlgorithm::Area => "area",
+            ScaleAlgorithm::Neighbor => "neighbor",
+        }
+    }
+}
+
+/// Output video codecs
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    /// VP9, good compression with wide browser support
+    #[default]
+    Vp9,
+    /// AV1, best compression but slower to encode
+    Av1,
+    /// VP8, for old players without VP9 support
+    Vp8,
+}
+
+impl std::str::FromStr for VideoCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "vp9" => Ok(VideoCodec::Vp9),
+            "av1" => Ok(VideoCodec::Av1),
+            "vp8" => Ok(VideoCodec::Vp8),
+            _ => Err(format!("Invalid video codec '{}', expected vp9, av1 or vp8", s)),
+        }
+    }
+}
+
+impl ParamChoice for VideoCodec {
+    const ALLOWED: &'static [&'static str] = &["vp9", "av1", "vp8"];
+}
+
+impl VideoCodec {
+    /// Human readable codec name
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoCodec::Vp9 => "VP9",
+            VideoCodec::Av1 => "AV1",
+            VideoCodec::Vp8 => "VP8",
+        }
+    }
+
+    /// ffmpeg encoders able to produce this codec, in order of preference
+    pub fn encoders(&self) -> &'static [VideoEncoder] {
+        match self {
+            VideoCodec::Vp9 => &[VideoEncoder::LibvpxVp9],
+            VideoCodec::Av1 => &[VideoEncoder::LibSvtAv1, VideoEncoder::LibaomAv1],
+            VideoCodec::Vp8 => &[VideoEncoder::LibvpxVp8],
+        }
+    }
+}
+
+/// ffmpeg video encoders used for WebM and animated output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoEncoder {
+    LibvpxVp9,
+    LibvpxVp8,
+    LibSvtAv1,
+    LibaomAv1,
+    LibWebp,
+    Gif,
+}
+
+impl VideoEncoder {
+    /// Encoder name as passed to `-c:v`
+    pub fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            VideoEncoder::LibvpxVp9 => "libvpx-vp9",
+            VideoEncoder::LibvpxVp8 => "libvpx",
+            VideoEncoder::LibSvtAv1 => "libsvtav1",
+            VideoEncoder::LibaomAv1 => "libaom-av1",
+            VideoEncoder::LibWebp => "libwebp",
+            VideoEncoder::Gif => "gif",
+        }
+    }
+
+    /// Rate-control and speed arguments for the given quality level and bitrate
+    ///
+    /// Without a bitrate the encoder runs in constant-quality (CRF) mode, using
+    /// `crf` instead of the preset's CRF when given.
+    pub fn quality_args(&self, quality: &CompressionQuality, bitrate: Option<&VideoBitrate>, crf: Option<u8>) -> Vec<String> {
+        let mut args: Vec<String> = match (self, bitrate) {
+            // Animation encoders have no bitrate control
+            (VideoEncoder::LibWebp, _) => vec![
+                "-lossless".into(), "0".into(),
+                "-q:v".into(), quality.webp_quality().to_string(),
+            ],
+            (VideoEncoder::Gif, _) => vec![],
+            (_, Some(bitrate)) => bitrate.args(*self),
+            (VideoEncoder::LibvpxVp9, None) => vec![
+                "-crf".into(), crf.unwrap_or_else(|| quality.crf_value()).to_string(),
+                "-b:v".into(), "0".into(),   // Use CRF mode for consistent quality
+            ],
+            // libvpx VP8 needs a bitrate ceiling alongside CRF
+            (VideoEncoder::LibvpxVp8, None) => vec![
+                "-crf".into(), crf.unwrap_or_else(|| quality.vp8_crf_value()).to_string(),
+                "-b:v".into(), quality.vp8_max_bitrate().into(),
+                "-qmin".into(), "0".into(),
+                "-qmax".into(), "63".into(),
+            ],
+            (VideoEncoder::LibSvtAv1, None) => vec![
+                "-crf".into(), crf.unwrap_or_else(|| quality.av1_crf_value()).to_string(),
+            ],
+            (VideoEncoder::LibaomAv1, None) => vec![
+                "-crf".into(), crf.unwrap_or_else(|| quality.av1_crf_value()).to_string(),
+                "-b:v".into(), "0".into(),
+            ],
+        };
+
+        // Speed and threading settings
+        args.extend(match self {
+            VideoEncoder::LibvpxVp9 => vec![
+                "-deadline".into(), quality.deadline().into(),
+                "-cpu-used".into(), quality.cpu_used().to_string(),
+                "-row-mt".into(), "1".into(),   // Enable row-based multithreading for faster encoding
+                "-tile-columns".into(), "2".into(),   // Tile encoding for parallelization
+                "-tile-rows".into(), "1".into(),
+            ],
+            VideoEncoder::LibvpxVp8 => vec![
+                "-deadline".into(), quality.deadline().into(),
+                "-cpu-used".into(), quality.cpu_used().to_string(),
+            ],
+            VideoEncoder::LibSvtAv1 => vec![
+                "-preset".into(), quality.svt_av1_preset().to_string(),
+            ],
+            VideoEncoder::LibaomAv1 => vec![
+                "-cpu-used".into(), quality.aom_cpu_used().to_string(),
+                "-row-mt".into(), "1".into(),
+                "-tiles".into(), "2x2".into(),
+            ],
+            VideoEncoder::LibWebp => vec![
+                "-compression_level".into(), "4".into(),
+            ],
+            VideoEncoder::Gif => vec![],
+        });
+
+        args
+    }
+
+    /// Whether bitrate mode is encoded in two passes with this encoder
+    pub fn supports_two_pass(&self) -> bool {
+        !matches!(self, VideoEncoder::LibSvtAv1)
+    }
+
+    /// CRFs sampled by the `target_vmaf` search, lowest first; empty for animation encoders
+    pub fn crf_probes(&self) -> &'static [u8] {
+        match self {
+            VideoEncoder::LibvpxVp9 => &[24, 32, 40, 48],
+            VideoEncoder::LibvpxVp8 => &[8, 16, 24, 32],
+            VideoEncoder::LibSvtAv1 | VideoEncoder::LibaomAv1 => &[24, 34, 44, 54],
+            VideoEncoder::LibWebp | VideoEncoder::Gif => &[],
+        }
+    }
+
+    /// CRFs the `target_vmaf` search may pick, extrapolating beyond the probes
+    pub fn crf_range(&self) -> (u8, u8) {
+        match self {
+            VideoEncoder::LibvpxVp9 => (15, 55),
+            VideoEncoder::LibvpxVp8 => (4, 40),
+            VideoEncoder::LibSvtAv1 | VideoEncoder::LibaomAv1 => (15, 60),
+            VideoEncoder::LibWebp | VideoEncoder::Gif => (0, 0),
+        }
+    }
+}
+
+/// VMAF of the sample clip encoded at one CRF
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct CrfSample {
+    pub crf: u8,
+    pub vmaf: f64,
+}
+
+/// Outcome of the `target_vmaf` CRF search
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct CrfSearch {
+    pub target_vmaf: f64,
+    /// CRF used for the full encode
+    pub crf: u8,
+    /// Mean VMAF interpolated from the samples at the chosen CRF
+    pub predicted_vmaf: f64,
+    pub samples: Vec<CrfSample>,
+}
+
+impl CrfSearch {
+    /// Pick the highest CRF in `range` whose interpolated VMAF still meets the target
+    ///
+    /// VMAF is interpolated linearly between the samples and extrapolated from
+    /// the outermost pair. When no CRF reaches the target, the lowest one is used.
+    pub fn pick(target_vmaf: f64, mut samples: Vec<CrfSample>, range: (u8, u8)) -> Self {
+        samples.sort_by_key(|sample| sample.crf);
+        let predict = |crf: f64| -> f64 {
+            let (a, b) = match samples.as_slice() {
+                [] => return 0.0,
+                [only] => return only.vmaf,
+                all => {
+                    let i = all.windows(2).position(|pair| crf <= pair[1].crf as f64).unwrap_or(all.len() - 2);
+                    (all[i], all[i + 1])
+                }
+            };
+            let slope = (b.vmaf - a.vmaf) / (b.crf as f64 - a.crf as f64);
+            (a.vmaf + slope * (crf - a.crf as f64)).clamp(0.0, 100.0)
+        };
+
+        let crf = (range.0..=range.1)
+            .rev()
+            .find(|crf| predict(*crf as f64) >= target_vmaf)
+            .unwrap_or(range.0);
+        let predicted_vmaf = (predict(crf as f64) * 100.0).round() / 100.0;
+        Self { target_vmaf, crf, predicted_vmaf, samples }
+    }
+}
+
+/// Video rate-control modes
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RateControl {
+    /// Constant quality driven by the `CompressionQuality` CRF presets
+    #[default]
+    Crf,
+    /// Average bitrate with optional bounds, encoded in two passes where supported
+    Bitrate(VideoBitrate),
+    /// Bitrate derived from the input duration so the output stays below a size limit
+    TargetSize { max_size_mb: f64 },
+    /// CRF found by encoding and scoring samples so the output reaches a mean VMAF
+    TargetVmaf { vmaf: f64 },
+}
+
+impl RateControl {
+    /// Lowest and highest accepted `target_vmaf`
+    pub const MIN_TARGET_VMAF: f64 = 50.0;
+    pub const MAX_TARGET_VMAF: f64 = 99.0;
+}
+
+/// Target video bitrate with optional bounds, all in kbit/s
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct VideoBitrate {
+    pub target_kbps: u32,
+    pub min_kbps: Option<u32>,
+    pub max_kbps: Option<u32>,
+}
+
+impl VideoBitrate {
+    /// Lowest accepted video bitrate
+    pub const MIN_KBPS: u32 = 50;
+    /// Highest accepted video bitrate
+    pub const MAX_KBPS: u32 = 100_000;
+
+    /// Check the bitrate range and ordering
+    pub fn validate(&self) -> Result<(), ParamError> {
+        let fields = [
+            ("video_bitrate", Some(self.target_kbps)),
+            ("min_bitrate", self.min_kbps),
+            ("max_bitrate", self.max_kbps),
+        ];
+        for (field, kbps) in fields {
+            if let Some(kbps) = kbps.filter(|kbps| !(Self::MIN_KBPS..=Self::MAX_KBPS).contains(kbps)) {
+                let message = format!(
+                    "Video bitrate must be between {}k and {}k, got {}k",
+                    Self::MIN_KBPS, Self::MAX_KBPS, kbps
+                );
+                return Err(ParamError::range(field, message, format!("{}k", Self::MIN_KBPS), format!("{}k", Self::MAX_KBPS)));
+            }
+        }
+        let message = "Video bitrates must satisfy min_bitrate <= video_bitrate <= max_bitrate";
+        let target = format!("{}k", self.target_kbps);
+        if self.min_kbps.is_some_and(|min| min > self.target_kbps) {
+            return Err(ParamError::range("min_bitrate", message, format!("{}k", Self::MIN_KBPS), target));
+        }
+        if self.max_kbps.is_some_and(|max| max < self.target_kbps) {
+            return Err(ParamError::range("max_bitrate", message, target, format!("{}k", Self::MAX_KBPS)));
+        }
+        Ok(())
+    }
+
+    fn args(&self, encoder: VideoEncoder) -> Vec<String> {
+        let mut args = vec!["-b:v".to_string(), format!("{}k", self.target_kbps)];
+        // SVT-AV1 only honours the average bitrate in VBR mode
+        if encoder != VideoEncoder::LibSvtAv1 {
+            if let Some(min) = self.min_kbps {
+                args.extend(["-minrate".to_string(), format!("{}k", min)]);
+            }
+            if let Some(max) = self.max_kbps {
+                args.extend(["-maxrate".to_string(), format!("{}k", max)]);
+            }
+        }
+        args
+    }
+}
+
+/// Parse a bitrate such as "64k", "1.5M" or "128000" into kbit/s
+pub fn parse_bitrate_kbps(value: &str) -> Option<u32> {
+    let value = value.trim().to_lowercase();
+    let (number, multiplier) = if let Some(n) = value.strip_suffix('k') {
+        (n, 1.0)
+    } else if let Some(n) = value.strip_suffix('m') {
+        (n, 1000.0)
+    } else {
+        (value.as_str(), 0.001)
+    };
+
+    let kbps = number.trim().parse::<f64>().ok()? * multiplier;
+    (kbps.is_finite() && kbps > 0.0 && kbps <= u32::MAX as f64).then(|| kbps.round() as u32)
 }
 
 /// Compression quality levels
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum CompressionQuality {
     /// Maximum compression, smallest file size (CRF 35-40)
@@ -55,11 +2050,44 @@ impl Default for VideoCompressionSettings {
     fn default() -> Self {
         Self {
             quality: CompressionQuality::High,
-            audio_bitrate: Some("64k".to_string()),
+            video_codec: VideoCodec::Vp9,
+            rate_control: RateControl::Crf,
+            audio: VideoAudioSettings::default(),
+            size_policy: SizePolicy::Always,
+            max_width: None,
+            max_height: None,
+            fps: None,
+            scale_algorithm: ScaleAlgorithm::Bicubic,
+            segments: Vec::new(),
+            poster: None,
+            output_format: VideoOutputFormat::WebM,
+            loop_count: 0,
+            palette_colors: 256,
+            dither: Dither::Sierra2_4a,
+            quality_metrics: false,
         }
     }
 }
 
+impl std::str::FromStr for CompressionQuality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "maximum" => Ok(CompressionQuality::Maximum),
+            "high" => Ok(CompressionQuality::High),
+            "balanced" => Ok(CompressionQuality::Balanced),
+            "low" => Ok(CompressionQuality::Low),
+            "minimal" => Ok(CompressionQuality::Minimal),
+            _ => Err(format!("Invalid quality '{}', expected maximum, high, balanced, low or minimal", s)),
+        }
+    }
+}
+
+impl ParamChoice for CompressionQuality {
+    const ALLOWED: &'static [&'static str] = &["maximum", "high", "balanced", "low", "minimal"];
+}
+
 impl CompressionQuality {
     /// Get CRF value for the quality setting
     pub fn crf_value(&self) -> u8 {
@@ -93,4 +2121,292 @@ impl CompressionQuality {
             CompressionQuality::Minimal => "best",
         }
     }
+
+    /// Get CRF value for VP8 (libvpx, range 4-63)
+    pub fn vp8_crf_value(&self) -> u8 {
+        match self {
+            CompressionQuality::Maximum => 40,
+            CompressionQuality::High => 32,
+            CompressionQuality::Balanced => 24,
+            CompressionQuality::Low => 16,
+            CompressionQuality::Minimal => 10,
+        }
+    }
+
+    /// Get the VP8 bitrate ceiling used together with CRF
+    pub fn vp8_max_bitrate(&self) -> &'static str {
+        match self {
+            CompressionQuality::Maximum => "1M",
+            CompressionQuality::High => "2M",
+            CompressionQuality::Balanced => "4M",
+            CompressionQuality::Low => "8M",
+            CompressionQuality::Minimal => "12M",
+        }
+    }
+
+    /// Get CRF value for AV1 (libsvtav1 and libaom-av1, range 0-63)
+    pub fn av1_crf_value(&self) -> u8 {
+        match self {
+            CompressionQuality::Maximum => 45,
+            CompressionQuality::High => 38,
+            CompressionQuality::Balanced => 32,
+            CompressionQuality::Low => 27,
+            CompressionQuality::Minimal => 23,
+        }
+    }
+
+    /// Get SVT-AV1 preset (0-13, higher is faster)
+    pub fn svt_av1_preset(&self) -> u8 {
+        match self {
+            CompressionQuality::Maximum => 10,
+            CompressionQuality::High => 8,
+            CompressionQuality::Balanced => 7,
+            CompressionQuality::Low => 6,
+            CompressionQuality::Minimal => 5,
+        }
+    }
+
+    /// Get libaom-av1 CPU usage setting (0-8, higher is faster)
+    pub fn aom_cpu_used(&self) -> u8 {
+        match self {
+            CompressionQuality::Maximum => 8,
+            CompressionQuality::High => 6,
+            CompressionQuality::Balanced => 5,
+            CompressionQuality::Low => 4,
+            CompressionQuality::Minimal => 3,
+        }
+    }
+
+    /// Typical VP9 bits per pixel per frame at this level's CRF, used for size estimates
+    pub fn bits_per_pixel(&self) -> f64 {
+        match self {
+            CompressionQuality::Maximum => 0.02,
+            CompressionQuality::High => 0.035,
+            CompressionQuality::Balanced => 0.05,
+            CompressionQuality::Low => 0.08,
+            CompressionQuality::Minimal => 0.1,
+        }
+    }
+
+    /// All levels, from smallest to largest output
+    pub const ALL: [CompressionQuality; 5] = [
+        CompressionQuality::Maximum,
+        CompressionQuality::High,
+        CompressionQuality::Balanced,
+        CompressionQuality::Low,
+        CompressionQuality::Minimal,
+    ];
+
+    /// Get libwebp quality (0-100) for animated WebP output
+    pub fn webp_quality(&self) -> u8 {
+        match self {
+            CompressionQuality::Maximum => 50,
+            CompressionQuality::High => 65,
+            CompressionQuality::Balanced => 75,
+            CompressionQuality::Low => 85,
+            CompressionQuality::Minimal => 92,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Allowed;
+
+    #[test]
+    fn streams_of_skips_cover_art() {
+        let probe: ProbeOutput = serde_json::from_str(
+            r#"{"streams":[
+                {"index":0,"codec_type":"video","codec_name":"h264","disposition":{"attached_pic":0}},
+                {"index":1,"codec_type":"audio","codec_name":"aac"},
+                {"index":2,"codec_type":"video","codec_name":"mjpeg","disposition":{"attached_pic":1}}
+            ]}"#,
+        )
+        .unwrap();
+
+        let video: Vec<_> = probe.streams_of("video").filter_map(|s| s.codec_name.as_deref()).collect();
+        assert_eq!(video, ["h264"]);
+        assert_eq!(probe.streams_of("audio").count(), 1);
+    }
+
+    #[test]
+    fn parses_pad_targets() {
+        assert_eq!("16:9".parse(), Ok(PadTarget::Aspect { width: 16, height: 9 }));
+        assert_eq!(" 1200X800 ".parse(), Ok(PadTarget::Size { width: 1200, height: 800 }));
+        assert!("0:1".parse::<PadTarget>().is_err());
+        assert!("1200".parse::<PadTarget>().is_err());
+        assert!("axb".parse::<PadTarget>().is_err());
+    }
+
+    #[test]
+    fn parses_pad_backgrounds() {
+        assert_eq!("transparent".parse(), Ok(PadBackground::Transparent));
+        assert_eq!("#ff8000".parse(), Ok(PadBackground::Color([255, 128, 0, 255])));
+        assert_eq!("00000080".parse(), Ok(PadBackground::Color([0, 0, 0, 128])));
+        assert!("#fff".parse::<PadBackground>().is_err());
+        assert!("#gggggg".parse::<PadBackground>().is_err());
+
+        assert!(PadBackground::Transparent.has_alpha());
+        assert!(PadBackground::Color([0, 0, 0, 128]).has_alpha());
+        assert!(!PadBackground::default().has_alpha());
+    }
+
+    #[test]
+    fn gravity_places_content() {
+        let (canvas, content) = ((100, 60), (40, 20));
+        assert_eq!(Gravity::Center.offset(canvas, content), (30, 20));
+        assert_eq!(Gravity::NorthWest.offset(canvas, content), (0, 0));
+        assert_eq!(Gravity::SouthEast.offset(canvas, content), (60, 40));
+        assert_eq!(Gravity::East.offset(canvas, content), (60, 20));
+        assert_eq!(Gravity::South.offset(canvas, content), (30, 40));
+        // Content larger than the canvas is never placed at a negative offset
+        assert_eq!(Gravity::Center.offset((10, 10), (20, 20)), (0, 0));
+
+        assert_eq!("top-left".parse(), Ok(Gravity::NorthWest));
+        assert!("middle".parse::<Gravity>().is_err());
+    }
+
+    #[test]
+    fn parses_filter_chains() {
+        let filters = ImageFilter::parse_list("sharpen, blur:2, brightness:-10,grayscale,").unwrap();
+        assert_eq!(
+            filters,
+            [
+                ImageFilter::Sharpen { sigma: 0.5, threshold: 1 },
+                ImageFilter::Blur { sigma: 2.0 },
+                ImageFilter::Brightness(-10),
+                ImageFilter::Grayscale,
+            ]
+        );
+        assert_eq!(ImageFilter::parse_list("").unwrap(), []);
+
+        assert!(ImageFilter::parse_list("brightness").is_err());
+        assert!(ImageFilter::parse_list("gamma:20").is_err());
+        assert!(ImageFilter::parse_list("invert:1").is_err());
+        assert!(ImageFilter::parse_list("contrast:nan").is_err());
+        assert!(ImageFilter::parse_list("emboss").is_err());
+        assert!(ImageFilter::parse_list(&vec!["invert"; ImageFilter::MAX_PER_REQUEST + 1].join(",")).is_err());
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("12.5"), Ok(12.5));
+        assert_eq!(parse_timestamp(" 1:30 "), Ok(90.0));
+        assert_eq!(parse_timestamp("01:02:03.5"), Ok(3723.5));
+        assert_eq!(parse_timestamp("90:00"), Ok(5400.0));
+
+        for invalid in ["", "-1", "1:60", "1:60:00", "1:2:3:4", "inf", "1.5:00", "abc"] {
+            assert!(parse_timestamp(invalid).is_err(), "{invalid} should be rejected");
+        }
+    }
+
+    #[test]
+    fn parses_trim_segments() {
+        let segments = TrimSegment::parse_list("0:05-0:12, 1:30-1:45,").unwrap();
+        assert_eq!(
+            segments,
+            [TrimSegment { start: 5.0, end: Some(12.0) }, TrimSegment { start: 90.0, end: Some(105.0) }]
+        );
+        assert_eq!(segments[0].length(None), Some(7.0));
+        assert_eq!(TrimSegment { start: 5.0, end: None }.length(Some(20.0)), Some(15.0));
+
+        assert!(TrimSegment::parse_list("").is_err());
+        assert!(TrimSegment::parse_list("5").is_err());
+        assert!(TrimSegment::parse_list("10-5").is_err());
+        assert!(TrimSegment::parse_list("5-5").is_err());
+        let too_many = vec!["0-1"; TrimSegment::MAX_SEGMENTS + 1].join(",");
+        assert!(TrimSegment::parse_list(&too_many).is_err());
+    }
+
+    #[test]
+    fn parses_bitrates() {
+        assert_eq!(parse_bitrate_kbps("64k"), Some(64));
+        assert_eq!(parse_bitrate_kbps(" 1.5M "), Some(1500));
+        assert_eq!(parse_bitrate_kbps("128000"), Some(128));
+        assert_eq!(parse_bitrate_kbps("96K"), Some(96));
+
+        for invalid in ["", "k", "0", "-64k", "abc", "nank", "1e10m"] {
+            assert_eq!(parse_bitrate_kbps(invalid), None, "{invalid} should be rejected");
+        }
+    }
+
+    #[test]
+    fn validates_video_bitrates() {
+        let bitrate = |target_kbps, min_kbps, max_kbps| VideoBitrate { target_kbps, min_kbps, max_kbps };
+        assert!(bitrate(1000, None, None).validate().is_ok());
+        assert!(bitrate(1000, Some(500), Some(2000)).validate().is_ok());
+        assert!(bitrate(1000, Some(1000), Some(1000)).validate().is_ok());
+
+        let field = |bitrate: VideoBitrate| bitrate.validate().unwrap_err().field;
+        assert_eq!(field(bitrate(VideoBitrate::MIN_KBPS - 1, None, None)), "video_bitrate");
+        assert_eq!(field(bitrate(1000, None, Some(VideoBitrate::MAX_KBPS + 1))), "max_bitrate");
+        assert_eq!(field(bitrate(1000, Some(2000), None)), "min_bitrate");
+        assert_eq!(field(bitrate(1000, None, Some(500))), "max_bitrate");
+
+        let error = bitrate(1000, Some(2000), None).validate().unwrap_err();
+        assert!(matches!(error.allowed, Allowed::Range { ref min, ref max } if min == "50k" && max == "1000k"));
+    }
+
+    fn samples(points: &[(u8, f64)]) -> Vec<CrfSample> {
+        points.iter().map(|&(crf, vmaf)| CrfSample { crf, vmaf }).collect()
+    }
+
+    #[test]
+    fn crf_search_interpolates_between_samples() {
+        let search = CrfSearch::pick(93.0, samples(&[(40, 88.0), (20, 98.0), (30, 94.0)]), (15, 55));
+        assert_eq!(search.crf, 31);
+        assert_eq!(search.predicted_vmaf, 93.4);
+        assert_eq!(search.samples, samples(&[(20, 98.0), (30, 94.0), (40, 88.0)]));
+    }
+
+    #[test]
+    fn crf_search_extrapolates_beyond_samples() {
+        let points = [(20, 98.0), (30, 94.0), (40, 88.0)];
+        let high = CrfSearch::pick(99.0, samples(&points), (15, 55));
+        assert_eq!((high.crf, high.predicted_vmaf), (17, 99.2));
+        let low = CrfSearch::pick(50.0, samples(&points), (15, 55));
+        assert_eq!((low.crf, low.predicted_vmaf), (55, 79.0));
+    }
+
+    #[test]
+    fn crf_search_falls_back_to_the_lowest_crf() {
+        let unreachable = CrfSearch::pick(95.0, samples(&[(20, 60.0), (30, 50.0)]), (15, 55));
+        assert_eq!((unreachable.crf, unreachable.predicted_vmaf), (15, 65.0));
+        let empty = CrfSearch::pick(90.0, Vec::new(), (4, 40));
+        assert_eq!((empty.crf, empty.predicted_vmaf), (4, 0.0));
+        let single = CrfSearch::pick(80.0, samples(&[(30, 90.0)]), (15, 55));
+        assert_eq!((single.crf, single.predicted_vmaf), (55, 90.0));
+    }
+
+    #[test]
+    fn summarizes_scores() {
+        let mut scores: Vec<f64> = (1..=100).rev().map(f64::from).collect();
+        scores.push(f64::NAN);
+        assert_eq!(
+            ScoreSummary::from_scores(scores),
+            Some(ScoreSummary { mean: 50.5, min: 1.0, p1: 1.0, p5: 5.0, median: 50.0 })
+        );
+        assert_eq!(
+            ScoreSummary::from_scores(vec![0.123456]),
+            Some(ScoreSummary { mean: 0.1235, min: 0.1235, p1: 0.1235, p5: 0.1235, median: 0.1235 })
+        );
+        assert_eq!(ScoreSummary::from_scores(Vec::new()), None);
+        assert_eq!(ScoreSummary::from_scores(vec![f64::NAN]), None);
+    }
+
+    #[test]
+    fn single_dimension_caps_keep_even_sizes() {
+        let settings = VideoCompressionSettings { max_width: Some(641), ..Default::default() };
+        assert_eq!(
+            settings.video_filter(None).as_deref(),
+            Some("scale=w='trunc(min(iw,641)/2)*2':h=-2:flags=bicubic")
+        );
+
+        let settings = VideoCompressionSettings { max_height: Some(480), ..Default::default() };
+        assert_eq!(
+            settings.video_filter(None).as_deref(),
+            Some("scale=w=-2:h='trunc(min(ih,480)/2)*2':flags=bicubic")
+        );
+    }
 }
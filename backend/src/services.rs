@@ -2,7 +2,17 @@
 
 pub mod image_processor {
     use crate::errors::AppError;
-    use image::ImageFormat;
+    use crate::models::{
+        ColorProfileMode, ImageConversionOutput, ImageConversionSettings, ImageFilter, PadBackground, PadSettings,
+        PadTarget, WebpMode,
+    };
+    use image::codecs::png::PngDecoder;
+    use image::{imageops, DynamicImage, ImageDecoder, Rgba, RgbImage, RgbaImage};
+    use moxcms::{ColorProfile, DataColorSpace, Layout, ProfileText, TransformOptions};
+    use std::io::Cursor;
+
+    /// Largest width/height the WebP format can store
+    pub const WEBP_MAX_DIMENSION: u32 = 16383;
 
     /// Check if the provided bytes represent a valid PNG image
     pub fn is_valid_png(data: &[u8]) -> bool {
@@ -12,48 +22,19 @@ pub mod image_processor {
         }
         
         let png_signature = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
-        &data[0..8] == png_signature
-    }
-
-    /// Convert PNG image data to WebP format
-    /// 
-    /// This function:
-    /// 1. Validates the input PNG data
-    /// 2. Loads the image using the `image` crate
-    /// 3. Converts it to WebP format using the `webp` crate
-    /// 4. Returns the WebP bytes
-    pub async fn convert_png_to_webp(png_data: &[u8]) -> Result<Vec<u8>, AppError> {
-        tracing::info!("🔄 Starting PNG to WebP conversion");
-
-        // Load PNG image
-        let img = image::load_from_memory_with_format(png_data, ImageFormat::Png)
-            .map_err(|e| {
-                tracing::error!("Failed to decode PNG image: {}", e);
-                AppError::ProcessingError(format!("Failed to decode PNG: {}", e))
-            })?;
-
-        tracing::info!("📐 Image dimensions: {}x{}", img.width(), img.height());
-
-        // Convert to RGB format (WebP encoder expects RGB)
-        let rgb_img = img.to_rgb8();
-        let (width, height) = rgb_img.dimensions();
-
-        // Encode as WebP
-        let encoder = webp::Encoder::from_rgb(&rgb_img, width, height);
-        
-        // Use quality of 85 for good balance between file size and quality
-        let webp_data = encoder.encode(85.0);
-        
-        tracing::info!("✅ WebP conversion completed, output size: {} bytes", webp_data.len());
-
-        Ok(webp_data.to_vec())
+        data[0..8] == png_signature
     }
 
     /// Alternative conversion method using tokio for CPU-intensive tasks
     /// This runs the conversion in a blocking thread pool to avoid blocking the async runtime
-    pub async fn convert_png_to_webp_async(png_data: Vec<u8>) -> Result<Vec<u8>, AppError> {
+    ///
+    /// The PNG data is handed back alongside the result so callers need not copy it.
+    pub async fn convert_png_to_webp_async(
+        png_data: Vec<u8>,
+        settings: ImageConversionSettings,
+    ) -> Result<(ImageConversionOutput, Vec<u8>), AppError> {
         tokio::task::spawn_blocking(move || {
-            convert_png_to_webp_sync(&png_data)
+            convert_png_to_webp_sync(&png_data, &settings).map(|output| (output, png_data))
         })
         .await
         .map_err(|e| {
@@ -63,101 +44,2476 @@ pub mod image_processor {
     }
 
     /// Synchronous version of the conversion for use in blocking context
-    fn convert_png_to_webp_sync(png_data: &[u8]) -> Result<Vec<u8>, AppError> {
-        let img = image::load_from_memory_with_format(png_data, ImageFormat::Png)
-            .map_err(|e| AppError::ProcessingError(format!("Failed to decode PNG: {}", e)))?;
+    fn convert_png_to_webp_sync(
+        png_data: &[u8],
+        settings: &ImageConversionSettings,
+    ) -> Result<ImageConversionOutput, AppError> {
+        let (img, icc_profile) = load_png(png_data)?;
+
+        process_and_encode(img, icc_profile, settings)
+    }
+
+    /// Decode PNG bytes together with their embedded ICC profile, if any
+    fn load_png(png_data: &[u8]) -> Result<(DynamicImage, Option<Vec<u8>>), AppError> {
+        let decode_error = |e: image::ImageError| AppError::ProcessingError(format!("Failed to decode PNG: {}", e));
+
+        let mut decoder = PngDecoder::new(Cursor::new(png_data)).map_err(decode_error)?;
+        let icc_profile = decoder.icc_profile().unwrap_or_else(|e| {
+            tracing::warn!("Ignoring unreadable ICC profile: {}", e);
+            None
+        });
+        let img = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
+
+        Ok((img, icc_profile))
+    }
+
+    /// Apply the requested transformations and encode the result as WebP
+    fn process_and_encode(
+        img: DynamicImage,
+        icc_profile: Option<Vec<u8>>,
+        settings: &ImageConversionSettings,
+    ) -> Result<ImageConversionOutput, AppError> {
+        let mut img = img;
+
+        let profile = icc_profile.as_deref().and_then(|icc| match ColorProfile::new_from_slice(icc) {
+            Ok(profile) => Some(profile),
+            Err(e) => {
+                tracing::warn!("Ignoring invalid ICC profile: {}", e);
+                None
+            }
+        });
+        let source_profile = profile.as_ref().map(profile_name);
+        if let Some(name) = &source_profile {
+            tracing::info!("🎨 Embedded ICC profile: {}", name);
+        }
+
+        // WebP viewers only honour RGB profiles, and color filters work in sRGB terms,
+        // so anything else falls back to converting the pixels
+        let color_profile = match (settings.color_profile, &profile) {
+            (ColorProfileMode::Keep, Some(profile)) if profile.color_space != DataColorSpace::Rgb => {
+                tracing::info!("🎨 Cannot embed a {:?} ICC profile, converting to sRGB instead", profile.color_space);
+                ColorProfileMode::Srgb
+            }
+            (ColorProfileMode::Keep, Some(_)) if settings.filters.iter().any(ImageFilter::changes_color) => {
+                tracing::info!("🎨 Color filters requested, converting to sRGB instead of keeping the ICC profile");
+                ColorProfileMode::Srgb
+            }
+            (mode, _) => mode,
+        };
+
+        if let (Some(profile), ColorProfileMode::Srgb) = (&profile, color_profile) {
+            img = convert_to_srgb(img, profile);
+        }
+
+        // Downscale first so filters (e.g. a light unsharp mask) work on the final pixels
+        if let Some(PadSettings { target: PadTarget::Size { width, height }, .. }) = &settings.pad {
+            img = fit_within(img, *width, *height);
+        }
+
+        if !settings.filters.is_empty() {
+            img = apply_filters(img, &settings.filters);
+        }
+
+        let mut keep_alpha = false;
+        let img = match &settings.pad {
+            Some(pad) => {
+                keep_alpha = pad.background.has_alpha();
+                pad_image(&img, pad)?
+            }
+            None => img,
+        };
+
+        let mut output = match settings.mode {
+            WebpMode::Auto => encode_auto(&img, keep_alpha, settings.quality, settings.min_ssim)?,
+            mode => ImageConversionOutput {
+                webp_data: encode_webp(&img, keep_alpha, mode, settings.quality),
+                mode,
+                ssim: None,
+                source_profile: None,
+            },
+        };
+
+        if let (Some(icc), Some(_), ColorProfileMode::Keep) = (&icc_profile, &profile, color_profile) {
+            output.webp_data = embed_icc_profile(&output.webp_data, icc, img.width(), img.height(), keep_alpha)?;
+        }
+        output.source_profile = source_profile;
+
+        Ok(output)
+    }
+
+    /// Human readable name of an ICC profile, taken from its description tag
+    fn profile_name(profile: &ColorProfile) -> String {
+        let name = match &profile.description {
+            Some(ProfileText::PlainString(text)) => Some(text.clone()),
+            Some(ProfileText::Localizable(texts)) => texts
+                .iter()
+                .find(|t| t.language.eq_ignore_ascii_case("en"))
+                .or_else(|| texts.first())
+                .map(|t| t.value.clone()),
+            Some(ProfileText::Description(desc)) => Some(desc.ascii_string.clone()),
+            None => None,
+        };
+
+        name.map(|n| n.trim_end_matches('\0').trim().to_string())
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| "Unnamed ICC profile".to_string())
+    }
+
+    /// Convert pixels from an embedded ICC profile to sRGB
+    ///
+    /// Images are reduced to 8 bits per channel, which is what the WebP encoder
+    /// consumes anyway. Unsupported profiles leave the pixels untouched.
+    pub fn convert_to_srgb(img: DynamicImage, profile: &ColorProfile) -> DynamicImage {
+        let (width, height) = (img.width(), img.height());
+        let has_alpha = img.color().has_alpha();
+        let srgb = ColorProfile::new_srgb();
+
+        let (src, src_layout, dst_layout) = match (profile.color_space, has_alpha) {
+            (DataColorSpace::Rgb, true) => (img.to_rgba8().into_raw(), Layout::Rgba, Layout::Rgba),
+            (DataColorSpace::Rgb, false) => (img.to_rgb8().into_raw(), Layout::Rgb, Layout::Rgb),
+            (DataColorSpace::Gray, true) => (img.to_luma_alpha8().into_raw(), Layout::GrayAlpha, Layout::Rgba),
+            (DataColorSpace::Gray, false) => (img.to_luma8().into_raw(), Layout::Gray, Layout::Rgb),
+            (color_space, _) => {
+                tracing::warn!("Unsupported ICC color space {:?}, skipping sRGB conversion", color_space);
+                return img;
+            }
+        };
+
+        let pixel_count = (width as usize) * (height as usize);
+        let mut dst = vec![0u8; pixel_count * dst_layout.channels()];
+        let converted = profile
+            .create_transform_8bit(src_layout, &srgb, dst_layout, TransformOptions::default())
+            .and_then(|transform| transform.transform(&src, &mut dst));
+
+        if let Err(e) = converted {
+            tracing::warn!("Failed to convert ICC profile to sRGB, keeping original pixels: {}", e);
+            return img;
+        }
+
+        let converted = if dst_layout == Layout::Rgba {
+            RgbaImage::from_raw(width, height, dst).map(DynamicImage::ImageRgba8)
+        } else {
+            RgbImage::from_raw(width, height, dst).map(DynamicImage::ImageRgb8)
+        };
+        converted.unwrap_or(img)
+    }
+
+    /// Embed an ICC profile in a WebP file by wrapping it in the extended (VP8X) container
+    pub fn embed_icc_profile(
+        webp_data: &[u8],
+        icc_profile: &[u8],
+        width: u32,
+        height: u32,
+        has_alpha: bool,
+    ) -> Result<Vec<u8>, AppError> {
+        const ICC_FLAG: u8 = 0x20;
+        const ALPHA_FLAG: u8 = 0x10;
+
+        if webp_data.len() < 20 || &webp_data[0..4] != b"RIFF" || &webp_data[8..12] != b"WEBP" {
+            return Err(AppError::ProcessingError("Encoder produced an invalid WebP container".to_string()));
+        }
+
+        let mut iccp = Vec::with_capacity(icc_profile.len() + 9);
+        iccp.extend_from_slice(b"ICCP");
+        iccp.extend_from_slice(&(icc_profile.len() as u32).to_le_bytes());
+        iccp.extend_from_slice(icc_profile);
+        if icc_profile.len() % 2 == 1 {
+            iccp.push(0);
+        }
+
+        let mut out = Vec::with_capacity(webp_data.len() + iccp.len() + 18);
+        out.extend_from_slice(&webp_data[0..12]);
+
+        if &webp_data[12..16] == b"VP8X" {
+            // Already extended: set the ICC flag and insert ICCP right after the VP8X chunk
+            let mut vp8x = webp_data[12..30].to_vec();
+            vp8x[8] |= ICC_FLAG;
+            out.extend_from_slice(&vp8x);
+            out.extend_from_slice(&iccp);
+            out.extend_from_slice(&webp_data[30..]);
+        } else {
+            let mut flags = ICC_FLAG;
+            if has_alpha {
+                flags |= ALPHA_FLAG;
+            }
+            out.extend_from_slice(b"VP8X");
+            out.extend_from_slice(&10u32.to_le_bytes());
+            out.extend_from_slice(&[flags, 0, 0, 0]);
+            out.extend_from_slice(&(width - 1).to_le_bytes()[0..3]);
+            out.extend_from_slice(&(height - 1).to_le_bytes()[0..3]);
+            out.extend_from_slice(&iccp);
+            out.extend_from_slice(&webp_data[12..]);
+        }
+
+        let riff_size = (out.len() - 8) as u32;
+        out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+        Ok(out)
+    }
+
+    /// Encode both a lossless and a lossy WebP and keep the better candidate
+    ///
+    /// The lossy result wins only if it is smaller and its SSIM against the
+    /// source reaches `min_ssim`; otherwise the lossless result is returned.
+    fn encode_auto(
+        img: &DynamicImage,
+        keep_alpha: bool,
+        quality: f32,
+        min_ssim: f64,
+    ) -> Result<ImageConversionOutput, AppError> {
+        let lossless = encode_webp(img, keep_alpha, WebpMode::Lossless, quality);
+        let lossy = encode_webp(img, keep_alpha, WebpMode::Lossy, quality);
+
+        let decoded = webp::Decoder::new(&lossy)
+            .decode()
+            .ok_or_else(|| AppError::ProcessingError("Failed to decode lossy WebP candidate".to_string()))?
+            .to_image();
+        let lossy_ssim = ssim(img, &decoded);
+
+        tracing::info!("⚖️  Auto mode: lossless={} bytes, lossy={} bytes (SSIM {:.4}, min {:.4})",
+                       lossless.len(), lossy.len(), lossy_ssim, min_ssim);
+
+        let output = if lossy_ssim >= min_ssim && lossy.len() < lossless.len() {
+            ImageConversionOutput { webp_data: lossy, mode: WebpMode::Lossy, ssim: Some(lossy_ssim), source_profile: None }
+        } else {
+            ImageConversionOutput { webp_data: lossless, mode: WebpMode::Lossless, ssim: Some(1.0), source_profile: None }
+        };
+
+        Ok(output)
+    }
+
+    /// Encode an image as WebP, dropping the alpha channel unless asked to keep it
+    fn encode_webp(img: &DynamicImage, keep_alpha: bool, mode: WebpMode, quality: f32) -> Vec<u8> {
+        let (width, height) = (img.width(), img.height());
+
+        let pixels = if keep_alpha {
+            img.to_rgba8().into_raw()
+        } else {
+            // Convert to RGB format (WebP encoder expects RGB)
+            img.to_rgb8().into_raw()
+        };
+        let encoder = if keep_alpha {
+            webp::Encoder::from_rgba(&pixels, width, height)
+        } else {
+            webp::Encoder::from_rgb(&pixels, width, height)
+        };
+
+        match mode {
+            WebpMode::Lossless => encoder.encode_lossless().to_vec(),
+            _ => encoder.encode(quality).to_vec(),
+        }
+    }
+
+    /// Mean structural similarity (SSIM) of two images, computed on luma over 8x8 blocks
+    ///
+    /// Returns a value in [-1, 1] where 1.0 means the images are identical.
+    pub fn ssim(a: &DynamicImage, b: &DynamicImage) -> f64 {
+        const BLOCK: u32 = 8;
+        const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+        const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+        let a = a.to_luma8();
+        let b = b.to_luma8();
+        let width = a.width().min(b.width());
+        let height = a.height().min(b.height());
+
+        let mut total = 0.0;
+        let mut blocks = 0u64;
+
+        for by in (0..height).step_by(BLOCK as usize) {
+            for bx in (0..width).step_by(BLOCK as usize) {
+                let bw = BLOCK.min(width - bx);
+                let bh = BLOCK.min(height - by);
+                let n = (bw * bh) as f64;
+
+                let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+                for y in by..by + bh {
+                    for x in bx..bx + bw {
+                        let pa = a.get_pixel(x, y)[0] as f64;
+                        let pb = b.get_pixel(x, y)[0] as f64;
+                        sum_a += pa;
+                        sum_b += pb;
+                        sum_aa += pa * pa;
+                        sum_bb += pb * pb;
+                        sum_ab += pa * pb;
+                    }
+                }
+
+                let mean_a = sum_a / n;
+                let mean_b = sum_b / n;
+                let var_a = sum_aa / n - mean_a * mean_a;
+                let var_b = sum_bb / n - mean_b * mean_b;
+                let cov = sum_ab / n - mean_a * mean_b;
+
+                total += ((2.0 * mean_a * mean_b + C1) * (2.0 * cov + C2))
+                    / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+                blocks += 1;
+            }
+        }
+
+        if blocks == 0 {
+            1.0
+        } else {
+            total / blocks as f64
+        }
+    }
+
+    /// Extend the canvas to the requested aspect ratio or size
+    ///
+    /// For an exact size the image must already fit inside the canvas (see
+    /// `fit_within`); it is placed according to the configured gravity.
+    pub fn pad_image(img: &DynamicImage, pad: &PadSettings) -> Result<DynamicImage, AppError> {
+        let (width, height) = (img.width(), img.height());
+
+        let (canvas_w, canvas_h) = match pad.target {
+            PadTarget::Aspect { width: ratio_w, height: ratio_h } => {
+                // Smallest canvas with the target ratio that still contains the image
+                let (w, h, rw, rh) = (width as u64, height as u64, ratio_w as u64, ratio_h as u64);
+                if w * rh >= h * rw {
+                    (w, (w * rh).div_ceil(rw))
+                } else {
+                    ((h * rw).div_ceil(rh), h)
+                }
+            }
+            PadTarget::Size { width: target_w, height: target_h } => (target_w as u64, target_h as u64),
+        };
 
-        let rgb_img = img.to_rgb8();
-        let (width, height) = rgb_img.dimensions();
+        if canvas_w > WEBP_MAX_DIMENSION as u64 || canvas_h > WEBP_MAX_DIMENSION as u64 {
+            return Err(AppError::BadRequest(format!(
+                "Padded canvas {}x{} exceeds the WebP limit of {}px",
+                canvas_w, canvas_h, WEBP_MAX_DIMENSION
+            )));
+        }
+        let (canvas_w, canvas_h) = (canvas_w as u32, canvas_h as u32);
+
+        let fill = match pad.background {
+            PadBackground::Transparent => [0, 0, 0, 0],
+            PadBackground::Color(color) => color,
+        };
+
+        let (x, y) = pad.gravity.offset((canvas_w, canvas_h), (width, height));
+        tracing::info!("🖼️  Padding {}x{} -> {}x{} at offset ({}, {})",
+                       width, height, canvas_w, canvas_h, x, y);
+
+        let mut canvas = RgbaImage::from_pixel(canvas_w, canvas_h, Rgba(fill));
+        imageops::overlay(&mut canvas, &img.to_rgba8(), x as i64, y as i64);
+
+        Ok(DynamicImage::ImageRgba8(canvas))
+    }
+
+    /// Scale an image down (never up) so it fits inside the given bounds
+    fn fit_within(img: DynamicImage, max_width: u32, max_height: u32) -> DynamicImage {
+        if img.width() > max_width || img.height() > max_height {
+            img.resize(max_width, max_height, imageops::FilterType::Lanczos3)
+        } else {
+            img
+        }
+    }
+
+    /// Apply a chain of pixel filters in order
+    pub fn apply_filters(img: DynamicImage, filters: &[ImageFilter]) -> DynamicImage {
+        filters.iter().fold(img, |img, filter| {
+            tracing::info!("🎨 Applying filter: {:?}", filter);
+            apply_filter(img, filter)
+        })
+    }
 
-        let encoder = webp::Encoder::from_rgb(&rgb_img, width, height);
-        let webp_data = encoder.encode(85.0);
+    fn apply_filter(mut img: DynamicImage, filter: &ImageFilter) -> DynamicImage {
+        match *filter {
+            ImageFilter::Sharpen { sigma, threshold } => img.unsharpen(sigma, threshold),
+            ImageFilter::Blur { sigma } => img.blur(sigma),
+            ImageFilter::Brightness(value) => img.brighten(value),
+            ImageFilter::Contrast(value) => img.adjust_contrast(value),
+            ImageFilter::Gamma(gamma) => {
+                let lut: Vec<u8> = (0..=255u8)
+                    .map(|v| (255.0 * (v as f32 / 255.0).powf(1.0 / gamma)).round() as u8)
+                    .collect();
+                map_rgba(&img, |[r, g, b, a]| [lut[r as usize], lut[g as usize], lut[b as usize], a])
+            }
+            ImageFilter::Saturation(factor) => map_rgba(&img, |[r, g, b, a]| {
+                let luma = 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+                let adjust = |c: u8| (luma + (c as f32 - luma) * factor).round().clamp(0.0, 255.0) as u8;
+                [adjust(r), adjust(g), adjust(b), a]
+            }),
+            ImageFilter::Grayscale => img.grayscale(),
+            ImageFilter::Sepia => map_rgba(&img, |[r, g, b, a]| {
+                let (r, g, b) = (r as f32, g as f32, b as f32);
+                let tone = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+                [
+                    tone(0.393 * r + 0.769 * g + 0.189 * b),
+                    tone(0.349 * r + 0.686 * g + 0.168 * b),
+                    tone(0.272 * r + 0.534 * g + 0.131 * b),
+                    a,
+                ]
+            }),
+            ImageFilter::Invert => {
+                img.invert();
+                img
+            }
+        }
+    }
 
-        Ok(webp_data.to_vec())
+    /// Apply a per-pixel function to the RGBA representation of an image
+    fn map_rgba(img: &DynamicImage, f: impl Fn([u8; 4]) -> [u8; 4]) -> DynamicImage {
+        let mut rgba = img.to_rgba8();
+        for pixel in rgba.pixels_mut() {
+            pixel.0 = f(pixel.0);
+        }
+        DynamicImage::ImageRgba8(rgba)
     }
 }
 
 pub mod video_processor {
-    use crate::errors::AppError;
-    use crate::models::VideoCompressionSettings;
-    use std::io::Write;
+    use crate::errors::{AppError, ToolFailure};
+    use crate::models::{
+        AudioContainer, FfmpegCapabilities, ToolInfo, AudioConversionOutput, AudioSettings, AudioSource,
+        CompressionQuality, CrfSample, CrfSearch, ImageConversionOutput, ImageConversionSettings, JobProgress, LoudnessMeasurement,
+        Loudnorm, PosterFrame, ProbeOutput, ProbeStream, QualityMetrics, RateControl, ScoreSummary, SizeEstimate, TrimSegment, VideoBitrate, VideoCodec,
+        VideoCompressionSettings, VideoContainer, VideoConversionOutput, VideoEncoder, VideoOutputFormat,
+    };
+    use crate::services::image_processor;
+    use crate::config;
+    use crate::services::jobs::JobHandle;
+    use std::collections::VecDeque;
+    use std::path::Path;
+    use std::process::Stdio;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
     use tempfile::NamedTempFile;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::process::Command;
 
-    /// Convert MP4 bytes to WebM bytes using ffmpeg.
-    /// Runs ffmpeg in a blocking thread to avoid blocking the async runtime.
-    pub async fn convert_mp4_to_webm(mp4_data: Vec<u8>) -> Result<Vec<u8>, AppError> {
-        convert_mp4_to_webm_with_settings(mp4_data, VideoCompressionSettings::default()).await
-    }
+    /// Lines of ffmpeg's stderr kept while it runs
+    const STDERR_TAIL_LINES: usize = 40;
+    /// Lines of the stderr tail included in error responses
+    const DIAGNOSTIC_LINES: usize = 5;
+    /// Longest diagnostic line returned to clients
+    const DIAGNOSTIC_LINE_CHARS: usize = 200;
 
-    /// Convert MP4 bytes to WebM bytes using ffmpeg with custom compression settings.
-    pub async fn convert_mp4_to_webm_with_settings(
-        mp4_data: Vec<u8>,
+    /// Convert a video file (MP4, MOV, MKV, WebM, AVI or GIF) to WebM bytes,
+    /// publishing ffmpeg progress to the given job.
+    pub async fn convert_mp4_to_webm_with_progress(
+        input: &Path,
+        container: VideoContainer,
         settings: VideoCompressionSettings,
-    ) -> Result<Vec<u8>, AppError> {
-        tokio::task::spawn_blocking(move || {
-            // Create temp input file
-            let mut in_file = NamedTempFile::new().map_err(|e| AppError::ProcessingError(format!("Failed to create temp input file: {}", e)))?;
-            in_file.write_all(&mp4_data).map_err(|e| AppError::ProcessingError(format!("Failed to write input file: {}", e)))?;
-            let in_path = in_file.path().to_str().ok_or_else(|| AppError::ProcessingError("Invalid temp path".to_string()))?.to_string();
-
-            // Create temp output file with .webm extension
-            let out_file = NamedTempFile::with_suffix(".webm").map_err(|e| AppError::ProcessingError(format!("Failed to create temp output file: {}", e)))?;
-            let out_path = out_file.path().to_str().ok_or_else(|| AppError::ProcessingError("Invalid temp path".to_string()))?.to_string();
-
-            let crf = settings.quality.crf_value().to_string();
-            let cpu_used = settings.quality.cpu_used().to_string();
-            let deadline = settings.quality.deadline();
-            let audio_bitrate = settings.audio_bitrate.as_deref().unwrap_or("64k");
-
-            tracing::info!("🎬 Converting with settings: CRF={}, CPU-used={}, deadline={}, audio={}",
-                          crf, cpu_used, deadline, audio_bitrate);
-
-            // Build ffmpeg command with configurable compression settings
-            let status = std::process::Command::new("ffmpeg")
-                .arg("-i")
-                .arg(&in_path)
-                // Video encoding settings - VP9 for best compression
-                .arg("-c:v")
-                .arg("libvpx-vp9")
-                .arg("-crf")
-                .arg(&crf)
-                .arg("-b:v")
-                .arg("0")   // Use CRF mode for consistent quality
-                .arg("-deadline")
-                .arg(deadline)
-                .arg("-cpu-used")
-                .arg(&cpu_used)
-                .arg("-row-mt")
-                .arg("1")   // Enable row-based multithreading for faster encoding
-                .arg("-tile-columns")
-                .arg("2")   // Tile encoding for parallelization
-                .arg("-tile-rows")
-                .arg("1")
-                // Audio encoding settings - aggressive compression
-                .arg("-c:a")
-                .arg("libopus")
-                .arg("-b:a")
-                .arg(audio_bitrate)
-                .arg("-application")
-                .arg("voip") // Optimized for voice content
-                // Output format
-                .arg("-f")
-                .arg("webm")
-                .arg("-y")
-                .arg(&out_path)
-                .status()
-                .map_err(|e| AppError::ProcessingError(format!("Failed to execute ffmpeg: {}", e)))?;
-
-            if !status.success() {
-                return Err(AppError::ProcessingError(format!("ffmpeg exited with status: {}", status)));
-            }
-
-            // Read output file bytes
-            let out_bytes = std::fs::read(&out_path).map_err(|e| AppError::ProcessingError(format!("Failed to read output file: {}", e)))?;
-
-            Ok(out_bytes)
+        job: Option<&JobHandle>,
+    ) -> Result<VideoConversionOutput, AppError> {
+        // Create temp input file
+        let in_path = input.to_str().ok_or_else(|| AppError::ProcessingError("Invalid temp path".to_string()))?.to_string();
+
+        // Create temp output file with .webm extension
+        let out_file = NamedTempFile::with_suffix(format!(".{}", settings.output_format.extension())).map_err(|e| AppError::ProcessingError(format!("Failed to create temp output file: {}", e)))?;
+        let out_path = out_file.path().to_str().ok_or_else(|| AppError::ProcessingError("Invalid temp path".to_string()))?.to_string();
+
+        let probe = probe_media(&in_path).await?;
+        validate_probe(container, &probe)?;
+        let input_duration = probe.duration_secs();
+        tracing::info!("⏱️  Input {} duration: {:?}s", container.name(), input_duration);
+
+        // Progress and size targets refer to the trimmed output
+        let duration = validate_segments(&settings.segments, input_duration)?;
+
+        let animation = settings.output_format.is_animation();
+        if let Some(duration) = duration.filter(|d| animation && *d > VideoCompressionSettings::MAX_ANIMATION_SECS) {
+            return Err(AppError::BadRequest(format!(
+                "{} output is limited to {}s, trim the {:.1}s clip with start/end",
+                settings.output_format.name(),
+                VideoCompressionSettings::MAX_ANIMATION_SECS,
+                duration
+            )));
+        }
+
+        let encoder = resolve_encoder(settings.output_format, settings.video_codec).await?;
+
+        // Animations are silent
+        let has_audio = !animation && !settings.audio.mute && probe.streams_of("audio").next().is_some();
+        let capabilities = capabilities().await;
+        let audio_stream = probe.streams_of("audio").next().filter(|_| has_audio);
+        require_decoders(&capabilities, probe.streams_of("video").next().into_iter().chain(audio_stream))?;
+        if has_audio {
+            require_encoder(&capabilities, "libopus", "WebM audio")?;
+        }
+        if settings.audio.loudnorm.is_some() && has_audio {
+            require_filter(&capabilities, "loudnorm", "Loudness normalization")?;
+        }
+        // Checked before encoding so a missing filter does not waste the encode
+        if settings.quality_metrics {
+            for filter in ["scale2ref", "ssim", "psnr"] {
+                require_filter(&capabilities, filter, "Quality scoring")?;
+            }
+        }
+        let target_vmaf = match settings.rate_control {
+            RateControl::TargetVmaf { vmaf } => {
+                for filter in ["libvmaf", "scale2ref", "ssim", "psnr"] {
+                    require_filter(&capabilities, filter, "target_vmaf")?;
+                }
+                let input_duration = input_duration.ok_or_else(|| {
+                    AppError::BadRequest("Cannot search for a CRF: input duration is unknown".to_string())
+                })?;
+                Some((vmaf, input_duration))
+            }
+            _ => None,
+        };
+        let audio_kbps = if has_audio {
+            settings.audio.bitrate_kbps
+        } else {
+            0
+        };
+        let bitrate = resolve_bitrate(settings.rate_control, duration, audio_kbps)?;
+        let source_fps = probe.streams_of("video").find_map(|s| s.frame_rate());
+        let audio_filter = match settings.audio.loudnorm.filter(|_| has_audio) {
+            Some(loudnorm) => {
+                let measurement = measure_loudness(&input_args(&in_path, &settings.segments), &loudnorm, input_duration, job).await?;
+                Some(loudnorm.apply_filter(measurement.as_ref()))
+            }
+            None => None,
+        };
+        let fps_filter = settings.fps_filter(source_fps);
+        let mut options = EncodeOptions {
+            video_filter: settings.video_filter(source_fps),
+            audio_filter,
+            settings: &settings,
+            encoder,
+            bitrate,
+            has_audio,
+            input_duration,
+            crf: None,
+        };
+
+        // Bitrate mode runs an analysis pass first so the average bitrate is hit accurately
+        let two_pass = bitrate.is_some() && encoder.supports_two_pass();
+        let pass_dir = if two_pass {
+            Some(tempfile::tempdir().map_err(|e| AppError::ProcessingError(format!("Failed to create pass log directory: {}", e)))?)
+        } else {
+            None
+        };
+        let passlog = pass_dir.as_ref().map(|dir| dir.path().join("ffmpeg2pass").to_string_lossy().into_owned());
+
+        let passes: Vec<Option<u8>> = if two_pass { vec![Some(1), Some(2)] } else { vec![None] };
+        // Each CRF probe encodes and scores the samples, scoring the output decodes it again
+        let search_passes = if target_vmaf.is_some() { encoder.crf_probes().len() * 2 } else { 0 };
+        let total_passes = search_passes + passes.len() + usize::from(settings.quality_metrics);
+
+        let crf_search = match target_vmaf {
+            Some((target, input_duration)) => {
+                let search = search_crf(target, &in_path, &options, input_duration, fps_filter.as_deref(), job, total_passes).await?;
+                options.crf = Some(search.crf);
+                Some(search)
+            }
+            None => None,
+        };
+
+        for (index, pass) in passes.iter().enumerate() {
+            let pass = pass.zip(passlog.as_deref());
+            let args = build_ffmpeg_args(&in_path, &out_path, &options, pass);
+            run_ffmpeg(&args, duration, job, (search_passes + index, total_passes)).await?;
+        }
+
+        let out_size = output_size(&out_path).await?;
+
+        let quality_metrics = if settings.quality_metrics {
+            let reference = QualityReference {
+                in_path: &in_path,
+                segments: &settings.segments,
+                input_duration,
+                fps_filter,
+            };
+            Some(measure_quality(&reference, &out_path, duration, job, (search_passes + passes.len(), total_passes)).await?)
+        } else {
+            None
+        };
+
+        // Taken from the output so the poster matches its trimming and size
+        let poster_webp = match settings.poster {
+            Some(poster) => {
+                let output = encode_poster(&out_path, poster, duration, ImageConversionSettings::default()).await?;
+                Some(output.webp_data)
+            }
+            None => None,
+        };
+
+        Ok(VideoConversionOutput {
+            webm_file: out_file.into_temp_path(),
+            webm_size: out_size,
+            video_encoder: encoder,
+            video_bitrate_kbps: bitrate.map(|b| b.target_kbps),
+            poster_webp,
+            quality_metrics,
+            crf_search,
         })
-        .await
-        .map_err(|e| AppError::ProcessingError(format!("Conversion task join error: {}", e)))?
+    }
+
+    /// Extract a poster frame from a video file and encode it as WebP
+    pub async fn extract_poster(
+        input: &Path,
+        container: VideoContainer,
+        poster: PosterFrame,
+        settings: ImageConversionSettings,
+    ) -> Result<ImageConversionOutput, AppError> {
+        let in_path = input.to_str().ok_or_else(|| AppError::ProcessingError("Invalid temp path".to_string()))?.to_string();
+
+        let probe = probe_media(&in_path).await?;
+        validate_probe(container, &probe)?;
+        require_decoders(&*capabilities().await, probe.streams_of("video").next())?;
+
+        encode_poster(&in_path, poster, probe.duration_secs(), settings).await
+    }
+
+    /// Convert the audio track of a video, or a plain audio file, to an audio-only format
+    pub async fn convert_audio(
+        input: &Path,
+        source: AudioSource,
+        settings: &AudioSettings,
+        job: Option<&JobHandle>,
+    ) -> Result<AudioConversionOutput, AppError> {
+        let in_path = input.to_str().ok_or_else(|| AppError::ProcessingError("Invalid temp path".to_string()))?.to_string();
+
+        let out_file = NamedTempFile::with_suffix(format!(".{}", settings.format.extension())).map_err(|e| AppError::ProcessingError(format!("Failed to create temp output file: {}", e)))?;
+        let out_path = out_file.path().to_str().ok_or_else(|| AppError::ProcessingError("Invalid temp path".to_string()))?.to_string();
+
+        let probe = probe_media(&in_path).await?;
+        validate_audio_probe(source, &probe)?;
+        let duration = probe.duration_secs();
+        tracing::info!("⏱️  Input {} duration: {:?}s", source.name(), duration);
+
+        let format = settings.format;
+        let capabilities = require_ffmpeg().await?;
+        require_decoders(&capabilities, probe.streams_of("audio").next())?;
+        require_encoder(&capabilities, format.encoder(), &format!("{} output", format.name()))?;
+        if settings.loudnorm.is_some() {
+            require_filter(&capabilities, "loudnorm", "Loudness normalization")?;
+        }
+        let bitrate_kbps = settings.bitrate_kbps.unwrap_or_else(|| format.default_bitrate_kbps());
+        tracing::info!("🎧 Extracting audio: format={}, bitrate={}k, channels={:?}, sample_rate={:?}",
+                      format.name(), bitrate_kbps, settings.channels, settings.sample_rate);
+
+        // Drop video, subtitle and data streams (including MP3 cover art) and keep the first audio track
+        let mut args: Vec<String> = vec![
+            "-i".into(), in_path.clone(),
+            "-vn".into(), "-sn".into(), "-dn".into(),
+            "-map".into(), "0:a:0".into(),
+            "-c:a".into(), format.encoder().into(),
+            "-b:a".into(), format!("{}k", bitrate_kbps),
+        ];
+        if let Some(application) = settings.application {
+            args.extend(["-application".into(), application.ffmpeg_name().into()]);
+        }
+        if let Some(channels) = settings.channels {
+            args.extend(["-ac".into(), channels.to_string()]);
+        }
+
+        let mut sample_rate = settings.sample_rate;
+        if let Some(loudnorm) = &settings.loudnorm {
+            let measurement = measure_loudness(&input_args(&in_path, &[]), loudnorm, duration, job).await?;
+            args.extend(["-af".into(), loudnorm.apply_filter(measurement.as_ref())]);
+            // loudnorm upsamples to 192 kHz, go back to the source rate where the format allows
+            sample_rate = sample_rate.or_else(|| {
+                let source = probe.streams_of("audio").find_map(|s| s.sample_rate_hz()).unwrap_or(48000);
+                Some(if format.sample_rates().contains(&source) { source } else { 48000 })
+            });
+        }
+        if let Some(sample_rate) = sample_rate {
+            args.extend(["-ar".into(), sample_rate.to_string()]);
+        }
+        args.extend(["-f".into(), format.muxer().into(), "-y".into(), out_path.clone()]);
+
+        run_ffmpeg(&args, duration, job, (0, 1)).await?;
+
+        let audio_size = output_size(&out_path).await?;
+
+        Ok(AudioConversionOutput { audio_file: out_file.into_temp_path(), audio_size, bitrate_kbps })
+    }
+
+    /// Size of a file ffmpeg wrote
+    async fn output_size(path: &str) -> Result<u64, AppError> {
+        tokio::fs::metadata(path)
+            .await
+            .map(|metadata| metadata.len())
+            .map_err(|e| AppError::ProcessingError(format!("Failed to read output file: {}", e)))
+    }
+
+    /// Probe a video file for the inspect endpoint
+    pub async fn inspect_video(input: &Path, container: VideoContainer) -> Result<ProbeOutput, AppError> {
+        let in_path = input.to_str().ok_or_else(|| AppError::ProcessingError("Invalid temp path".to_string()))?.to_string();
+
+        let probe = probe_media(&in_path).await?;
+        validate_probe(container, &probe)?;
+        Ok(probe)
+    }
+
+    /// Estimate the WebM output size of each quality level
+    ///
+    /// This is a rough model of VP9 CRF output from the resolution, frame rate
+    /// and duration, capped at the source video bitrate. Returns nothing when
+    /// the duration or the video dimensions are unknown.
+    pub fn estimate_sizes(probe: &ProbeOutput, input_size: usize, audio_kbps: u32) -> Vec<SizeEstimate> {
+        let Some(duration) = probe.duration_secs() else {
+            return Vec::new();
+        };
+        let Some((pixels, fps, source_kbps)) = probe.streams_of("video").find_map(|stream| {
+            let pixels = stream.width? as f64 * stream.height? as f64;
+            Some((pixels, stream.frame_rate().unwrap_or(30.0), stream.bitrate_kbps()))
+        }) else {
+            return Vec::new();
+        };
+        let audio_kbps = if probe.streams_of("audio").next().is_some() { audio_kbps as f64 } else { 0.0 };
+
+        CompressionQuality::ALL
+            .iter()
+            .map(|&quality| {
+                let mut video_kbps = pixels * fps * quality.bits_per_pixel() / 1000.0;
+                if let Some(source_kbps) = source_kbps {
+                    video_kbps = video_kbps.min(source_kbps as f64);
+                }
+                let estimated_size = ((video_kbps + audio_kbps) * 1000.0 / 8.0 * duration) as u64;
+                SizeEstimate {
+                    quality,
+                    estimated_size,
+                    reduction_percent: (1.0 - estimated_size as f64 / input_size as f64) * 100.0,
+                }
+            })
+            .collect()
+    }
+
+    /// Frames the thumbnail filter compares when picking a representative poster
+    const POSTER_THUMBNAIL_FRAMES: u32 = 100;
+
+    /// Grab one frame of a media file as PNG and pass it through the WebP encoder
+    async fn encode_poster(
+        path: &str,
+        poster: PosterFrame,
+        duration: Option<f64>,
+        settings: ImageConversionSettings,
+    ) -> Result<ImageConversionOutput, AppError> {
+        let mut args: Vec<String> = Vec::new();
+        match poster {
+            PosterFrame::At(at) => {
+                if let Some(duration) = duration.filter(|duration| at >= *duration) {
+                    return Err(AppError::BadRequest(format!(
+                        "Poster timestamp {}s is past the end of the {:.3}s video",
+                        at, duration
+                    )));
+                }
+                args.extend(["-ss".into(), format!("{:.3}", at), "-i".into(), path.into()]);
+            }
+            PosterFrame::Auto => {
+                args.extend(["-i".into(), path.into()]);
+                args.extend(["-vf".into(), format!("thumbnail=n={}", POSTER_THUMBNAIL_FRAMES)]);
+            }
+        }
+        args.extend(["-map".into(), "0:V:0".into()]);
+
+        let png_file = NamedTempFile::with_suffix(".png").map_err(|e| AppError::ProcessingError(format!("Failed to create temp poster file: {}", e)))?;
+        let png_path = png_file.path().to_str().ok_or_else(|| AppError::ProcessingError("Invalid temp path".to_string()))?;
+        args.extend(["-frames:v".into(), "1".into(), "-c:v".into(), "png".into(), "-f".into(), "image2".into(), "-y".into(), png_path.into()]);
+
+        tracing::info!("🖼️  Extracting {:?} poster frame", poster);
+        run_ffmpeg(&args, None, None, (0, 1)).await?;
+
+        let png_data = tokio::fs::read(png_path).await.map_err(|e| AppError::ProcessingError(format!("Failed to read poster frame: {}", e)))?;
+        if png_data.is_empty() {
+            return Err(AppError::ProcessingError("ffmpeg did not produce a poster frame".to_string()));
+        }
+
+        image_processor::convert_png_to_webp_async(png_data, settings).await.map(|(output, _)| output)
+    }
+
+    /// Everything resolved from the settings and the probe that shapes the ffmpeg command
+    struct EncodeOptions<'a> {
+        settings: &'a VideoCompressionSettings,
+        encoder: VideoEncoder,
+        bitrate: Option<VideoBitrate>,
+        /// `-vf` chain for resizing and frame-rate limits
+        video_filter: Option<String>,
+        /// `-af` chain for loudness normalization
+        audio_filter: Option<String>,
+        /// Whether the output gets an audio track
+        has_audio: bool,
+        input_duration: Option<f64>,
+        /// CRF replacing the quality preset's, as found by the `target_vmaf` search
+        crf: Option<u8>,
+    }
+
+    /// Check trim segments against the probed input duration
+    ///
+    /// Returns the duration of the output: the summed segment lengths, or the
+    /// input duration when the video is not trimmed.
+    fn validate_segments(segments: &[TrimSegment], input_duration: Option<f64>) -> Result<Option<f64>, AppError> {
+        if segments.is_empty() {
+            return Ok(input_duration);
+        }
+
+        if let Some(input_duration) = input_duration {
+            for segment in segments {
+                if segment.start >= input_duration {
+                    return Err(AppError::BadRequest(format!(
+                        "Trim start {}s is past the end of the {:.3}s input",
+                        segment.start, input_duration
+                    )));
+                }
+                if let Some(end) = segment.end.filter(|end| *end > input_duration) {
+                    return Err(AppError::BadRequest(format!(
+                        "Trim end {}s is past the end of the {:.3}s input",
+                        end, input_duration
+                    )));
+                }
+            }
+        }
+
+        Ok(segments.iter().map(|segment| segment.length(input_duration)).sum())
+    }
+
+    /// Share of a size limit reserved for WebM container overhead
+    const CONTAINER_OVERHEAD: f64 = 0.03;
+
+    /// Turn the requested rate control into a concrete video bitrate, if any
+    fn resolve_bitrate(
+        rate_control: RateControl,
+        duration: Option<f64>,
+        audio_kbps: u32,
+    ) -> Result<Option<VideoBitrate>, AppError> {
+        match rate_control {
+            RateControl::Crf | RateControl::TargetVmaf { .. } => Ok(None),
+            RateControl::Bitrate(bitrate) => Ok(Some(bitrate)),
+            RateControl::TargetSize { max_size_mb } => {
+                let duration = duration.ok_or_else(|| {
+                    AppError::BadRequest("Cannot encode to a target size: input duration is unknown".to_string())
+                })?;
+
+                // 1 MB = 8000 kbit
+                let total_kbps = max_size_mb * 8000.0 * (1.0 - CONTAINER_OVERHEAD) / duration;
+                let video_kbps = total_kbps - audio_kbps as f64;
+                if video_kbps < VideoBitrate::MIN_KBPS as f64 {
+                    return Err(AppError::BadRequest(format!(
+                        "max_size_mb {} is too small for {:.1}s of video: it leaves {:.0}k for video after {}k audio (minimum {}k)",
+                        max_size_mb, duration, video_kbps, audio_kbps, VideoBitrate::MIN_KBPS
+                    )));
+                }
+
+                let target_kbps = video_kbps.min(VideoBitrate::MAX_KBPS as f64) as u32;
+                tracing::info!("📏 Target size {} MB over {:.1}s -> video bitrate {}k", max_size_mb, duration, target_kbps);
+                Ok(Some(VideoBitrate { target_kbps, min_kbps: None, max_kbps: None }))
+            }
+        }
+    }
+
+    /// Build the ffmpeg argument list for a WebM conversion
+    ///
+    /// `pass` is the pass number and log file prefix of a two-pass encode. The
+    /// first pass only analyses the video and writes no output.
+    fn build_ffmpeg_args(in_path: &str, out_path: &str, options: &EncodeOptions, pass: Option<(u8, &str)>) -> Vec<String> {
+        let settings = options.settings;
+        let encoder = options.encoder;
+        let video_filter = options.video_filter.as_deref();
+        let audio_bitrate = format!("{}k", settings.audio.bitrate_kbps);
+        let quality_args = encoder.quality_args(&settings.quality, options.bitrate.as_ref(), options.crf);
+
+        tracing::info!("🎬 Converting with settings: encoder={}, {}, filter={:?}, segments={}, audio={}, pass={:?}",
+                      encoder.ffmpeg_name(), quality_args.join(" "), video_filter, settings.segments.len(),
+                      audio_bitrate, pass.map(|(n, _)| n));
+
+        let mut args: Vec<String> = Vec::new();
+        let first_pass = matches!(pass, Some((1, _)));
+
+        match settings.segments.as_slice() {
+            segments @ ([] | [_]) => {
+                args.extend(input_args(in_path, segments));
+                // Map explicitly so attached cover art is never picked as the video stream
+                args.extend(["-map".into(), "0:V:0".into(), "-map".into(), "0:a:0?".into()]);
+                if let Some(filter) = video_filter {
+                    args.extend(["-vf".into(), filter.into()]);
+                }
+                if let Some(filter) = options.audio_filter.as_deref().filter(|_| options.has_audio && !first_pass) {
+                    args.extend(["-af".into(), filter.into()]);
+                }
+            }
+            segments => {
+                let with_audio = options.has_audio && !first_pass;
+                let audio_filter = options.audio_filter.as_deref();
+                let graph = concat_graph(segments, options.input_duration, video_filter, audio_filter, with_audio);
+                args.extend(["-i".into(), in_path.into(), "-filter_complex".into(), graph, "-map".into(), "[v]".into()]);
+                if with_audio {
+                    args.extend(["-map".into(), "[a]".into()]);
+                }
+            }
+        }
+
+        // Video encoding settings
+        args.extend(["-c:v".into(), encoder.ffmpeg_name().into()]);
+        args.extend(quality_args);
+
+        let format = settings.output_format;
+        if format.is_animation() {
+            // The GIF muxer counts repeats after the first play, -1 meaning play once
+            let loop_arg = match (format, settings.loop_count) {
+                (VideoOutputFormat::Gif, 0) => 0,
+                (VideoOutputFormat::Gif, 1) => -1,
+                (VideoOutputFormat::Gif, plays) => plays as i64 - 1,
+                (_, plays) => plays as i64,
+            };
+            args.extend(["-an".into(), "-loop".into(), loop_arg.to_string()]);
+            args.extend(["-f".into(), format.extension().into(), "-y".into(), out_path.into()]);
+            return args;
+        }
+
+        if let Some((number, passlog)) = pass {
+            args.extend(["-pass".into(), number.to_string(), "-passlogfile".into(), passlog.into()]);
+            if number == 1 {
+                let null_output = if cfg!(windows) { "NUL" } else { "/dev/null" };
+                args.extend(["-an".into(), "-f".into(), "null".into(), "-y".into(), null_output.into()]);
+                return args;
+            }
+        }
+
+        // Audio encoding settings
+        if options.has_audio {
+            let audio = &settings.audio;
+            args.extend([
+                "-c:a".into(), "libopus".into(),
+                "-b:a".into(), audio_bitrate,
+                "-application".into(), audio.application.ffmpeg_name().into(),
+            ]);
+            if let Some(channels) = audio.channels {
+                args.extend(["-ac".into(), channels.to_string()]);
+            }
+            if let Some(sample_rate) = audio.sample_rate {
+                args.extend(["-ar".into(), sample_rate.to_string()]);
+            }
+        } else {
+            args.push("-an".into());
+        }
+
+        // Output format
+        args.extend(["-f".into(), "webm".into(), "-y".into(), out_path.into()]);
+
+        args
+    }
+
+    /// Run the measurement pass of two-pass loudness normalization
+    ///
+    /// Multi-segment trims are measured over the whole input. Returns `None`
+    /// when ffmpeg reports no usable statistics, e.g. for silent input.
+    async fn measure_loudness(
+        input_args: &[String],
+        loudnorm: &Loudnorm,
+        duration: Option<f64>,
+        job: Option<&JobHandle>,
+    ) -> Result<Option<LoudnessMeasurement>, AppError> {
+        tracing::info!("🔊 Measuring loudness for {}", loudnorm.measure_filter());
+        let mut command = Command::new(&config::ffmpeg().ffmpeg_path);
+        command
+            .args(["-hide_banner", "-nostats"])
+            .args(input_args)
+            .args(["-vn", "-sn", "-dn", "-af", &loudnorm.measure_filter(), "-f", "null", "-"]);
+        let output = output_with_limits(command, config::ffmpeg().timeout_for(duration), job).await?;
+
+        // The statistics are the last JSON object on stderr
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            let lines: Vec<String> = stderr.lines().map(str::to_string).collect();
+            let tail = &lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..];
+            tracing::error!("Loudness measurement exited with status {}:\n{}", output.status, tail.join("\n"));
+            return Err(classify_ffmpeg_failure(&output.status.to_string(), tail));
+        }
+
+        let measurement = stderr
+            .rfind('{')
+            .and_then(|start| stderr[start..].find('}').map(|end| &stderr[start..=start + end]))
+            .and_then(|json| serde_json::from_str::<LoudnessMeasurement>(json).ok());
+        match measurement {
+            Some(m) => tracing::info!("🔊 Measured {} LUFS, {} dBTP, {} LU", m.input_i, m.input_tp, m.input_lra),
+            None => tracing::warn!("No usable loudness statistics, falling back to single-pass loudnorm"),
+        }
+        Ok(measurement)
+    }
+
+    /// PSNR reported for identical frames, which ffmpeg prints as `inf`
+    const PSNR_CAP_DB: f64 = 100.0;
+
+    /// The part of the source an output was encoded from
+    struct QualityReference<'a> {
+        in_path: &'a str,
+        segments: &'a [TrimSegment],
+        input_duration: Option<f64>,
+        /// Frame-rate cap applied to the output, applied to the source too
+        fps_filter: Option<String>,
+    }
+
+    /// Score an encoded video against its source with SSIM, PSNR and, when available, VMAF
+    ///
+    /// The source gets the same trimming and frame-rate cap as the output, and
+    /// the output is scaled back up to the source resolution before comparing.
+    async fn measure_quality(
+        reference: &QualityReference<'_>,
+        out_path: &str,
+        duration: Option<f64>,
+        job: Option<&JobHandle>,
+        pass: (usize, usize),
+    ) -> Result<QualityMetrics, AppError> {
+        let vmaf = capabilities().await.has_filter("libvmaf");
+        if !vmaf {
+            tracing::warn!("📐 ffmpeg lacks libvmaf, scoring with SSIM and PSNR only");
+        }
+
+        let log_dir = tempfile::tempdir().map_err(|e| AppError::ProcessingError(format!("Failed to create metrics directory: {}", e)))?;
+        let log_path = |name: &str| log_dir.path().join(name).to_string_lossy().into_owned();
+        let (ssim_log, psnr_log, vmaf_log) = (log_path("ssim.log"), log_path("psnr.log"), log_path("vmaf.json"));
+
+        // The source is input 0 so the trim graph can be reused, the output is input 1
+        let mut args: Vec<String> = Vec::new();
+        let mut chains = Vec::new();
+        match reference.segments {
+            segments @ ([] | [_]) => {
+                args.extend(input_args(reference.in_path, segments));
+                chains.push(format!("[0:V:0]{}[v]", reference.fps_filter.as_deref().unwrap_or("null")));
+            }
+            segments => {
+                args.extend(["-i".into(), reference.in_path.into()]);
+                chains.push(concat_graph(segments, reference.input_duration, reference.fps_filter.as_deref(), None, false));
+            }
+        }
+        args.extend(["-i".into(), out_path.into()]);
+
+        chains.push("[1:v]setpts=PTS-STARTPTS,format=yuv420p[encoded]".into());
+        chains.push("[v]setpts=PTS-STARTPTS,format=yuv420p[source]".into());
+        chains.push("[encoded][source]scale2ref=flags=bicubic[scaled][ref]".into());
+        chains.push("[scaled]format=yuv420p[dist]".into());
+        chains.push(format!("[ref]split={}[r0][r1]{}", if vmaf { 3 } else { 2 }, if vmaf { "[r2]" } else { "" }));
+        // Each metric filter passes the distorted frames through to the next
+        chains.push(format!("[dist][r0]ssim=stats_file={}[d1]", ssim_log));
+        chains.push(format!("[d1][r1]psnr=stats_file={}[d2]", psnr_log));
+        let last = if vmaf {
+            let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+            chains.push(format!("[d2][r2]libvmaf=log_fmt=json:log_path={}:n_threads={}[out]", vmaf_log, threads));
+            "[out]"
+        } else {
+            "[d2]"
+        };
+        args.extend(["-filter_complex".into(), chains.join(";"), "-map".into(), last.into(), "-f".into(), "null".into(), "-".into()]);
+
+        tracing::info!("📐 Scoring output quality (vmaf={})", vmaf);
+        run_ffmpeg(&args, duration, job, pass).await?;
+
+        let read_log = |path: String| async move {
+            tokio::fs::read_to_string(&path)
+                .await
+                .map_err(|e| AppError::ProcessingError(format!("Failed to read quality metrics: {}", e)))
+        };
+        let ssim = stats_values(&read_log(ssim_log).await?, "All:");
+        let psnr: Vec<f64> = stats_values(&read_log(psnr_log).await?, "psnr_avg:")
+            .into_iter()
+            .map(|db| db.min(PSNR_CAP_DB))
+            .collect();
+        let vmaf_scores = if vmaf { Some(vmaf_frame_scores(&read_log(vmaf_log).await?)?) } else { None };
+
+        let metrics = QualityMetrics {
+            frames: ssim.len(),
+            vmaf: vmaf_scores.and_then(ScoreSummary::from_scores),
+            ssim: ScoreSummary::from_scores(ssim),
+            psnr: ScoreSummary::from_scores(psnr),
+        };
+        tracing::info!("📐 Quality over {} frames: vmaf={:?}, ssim={:?}, psnr={:?}",
+                      metrics.frames,
+                      metrics.vmaf.as_ref().map(|s| s.mean),
+                      metrics.ssim.as_ref().map(|s| s.mean),
+                      metrics.psnr.as_ref().map(|s| s.mean));
+        Ok(metrics)
+    }
+
+    /// Number of sample windows encoded for each CRF probe
+    const CRF_SAMPLE_COUNT: usize = 3;
+    /// Length of each sample window in seconds
+    const CRF_SAMPLE_SECS: f64 = 4.0;
+
+    /// Source ranges sampled by the CRF search
+    ///
+    /// The windows are spread evenly over the output timeline and mapped back
+    /// into the trim segments they fall in. Outputs too short to sample are
+    /// used whole.
+    fn sample_windows(segments: &[TrimSegment], input_duration: f64) -> Vec<TrimSegment> {
+        let ranges: Vec<(f64, f64)> = if segments.is_empty() {
+            vec![(0.0, input_duration)]
+        } else {
+            segments.iter().map(|segment| (segment.start, segment.end.unwrap_or(input_duration))).collect()
+        };
+        let total: f64 = ranges.iter().map(|(start, end)| end - start).sum();
+        if total <= CRF_SAMPLE_COUNT as f64 * CRF_SAMPLE_SECS {
+            return ranges.into_iter().map(|(start, end)| TrimSegment { start, end: Some(end) }).collect();
+        }
+
+        (0..CRF_SAMPLE_COUNT)
+            .filter_map(|i| {
+                let mut offset = total * (i as f64 + 0.5) / CRF_SAMPLE_COUNT as f64;
+                let &(start, end) = ranges.iter().find(|(start, end)| {
+                    let inside = offset <= end - start;
+                    if !inside {
+                        offset -= end - start;
+                    }
+                    inside
+                })?;
+                let center = start + offset;
+                Some(TrimSegment {
+                    start: (center - CRF_SAMPLE_SECS / 2.0).max(start),
+                    end: Some((center + CRF_SAMPLE_SECS / 2.0).min(end)),
+                })
+            })
+            .collect()
+    }
+
+    /// Find the highest CRF whose output still reaches `target_vmaf`
+    ///
+    /// Encodes the sample windows at each of the encoder's probe CRFs, scores
+    /// them against the source and interpolates between the results. Reports
+    /// progress as the first `2 * probes` of `total_passes`.
+    async fn search_crf(
+        target_vmaf: f64,
+        in_path: &str,
+        options: &EncodeOptions<'_>,
+        input_duration: f64,
+        fps_filter: Option<&str>,
+        job: Option<&JobHandle>,
+        total_passes: usize,
+    ) -> Result<CrfSearch, AppError> {
+        let windows = sample_windows(&options.settings.segments, input_duration);
+        let sample_duration = validate_segments(&windows, Some(input_duration))?;
+        let sample_settings = VideoCompressionSettings { segments: windows, ..options.settings.clone() };
+        let reference = QualityReference {
+            in_path,
+            segments: &sample_settings.segments,
+            input_duration: Some(input_duration),
+            fps_filter: fps_filter.map(str::to_string),
+        };
+        tracing::info!("🎯 Searching CRF for VMAF {} over {} sample(s), {:.1}s",
+                      target_vmaf, sample_settings.segments.len(), sample_duration.unwrap_or_default());
+
+        let mut samples = Vec::new();
+        for (index, &crf) in options.encoder.crf_probes().iter().enumerate() {
+            let sample_file = NamedTempFile::with_suffix(".webm").map_err(|e| AppError::ProcessingError(format!("Failed to create temp sample file: {}", e)))?;
+            let sample_path = sample_file.path().to_str().ok_or_else(|| AppError::ProcessingError("Invalid temp path".to_string()))?;
+            let sample_options = EncodeOptions {
+                settings: &sample_settings,
+                encoder: options.encoder,
+                bitrate: None,
+                video_filter: options.video_filter.clone(),
+                audio_filter: None,
+                has_audio: false,
+                input_duration: Some(input_duration),
+                crf: Some(crf),
+            };
+            let args = build_ffmpeg_args(in_path, sample_path, &sample_options, None);
+            run_ffmpeg(&args, sample_duration, job, (index * 2, total_passes)).await?;
+
+            let metrics = measure_quality(&reference, sample_path, sample_duration, job, (index * 2 + 1, total_passes)).await?;
+            let vmaf = metrics
+                .vmaf
+                .ok_or_else(|| AppError::ProcessingError(format!("VMAF scoring of the CRF {} sample returned no frames", crf)))?
+                .mean;
+            tracing::info!("🎯 CRF {} -> VMAF {}", crf, vmaf);
+            samples.push(CrfSample { crf, vmaf });
+        }
+
+        let search = CrfSearch::pick(target_vmaf, samples, options.encoder.crf_range());
+        tracing::info!("🎯 Picked CRF {} (predicted VMAF {}) for target {}", search.crf, search.predicted_vmaf, target_vmaf);
+        Ok(search)
+    }
+
+    /// Per-frame values of `key` from an ssim/psnr `stats_file`, e.g. `All:0.9876` or `psnr_avg:inf`
+    fn stats_values(log: &str, key: &str) -> Vec<f64> {
+        log.lines()
+            .filter_map(|line| {
+                line.split_whitespace()
+                    .find_map(|token| token.strip_prefix(key))
+                    .and_then(|value| value.parse::<f64>().ok())
+            })
+            .collect()
+    }
+
+    /// Per-frame VMAF scores from libvmaf's JSON log
+    fn vmaf_frame_scores(log: &str) -> Result<Vec<f64>, AppError> {
+        let json: serde_json::Value = serde_json::from_str(log)
+            .map_err(|e| AppError::ProcessingError(format!("Failed to parse VMAF log: {}", e)))?;
+        Ok(json["frames"]
+            .as_array()
+            .map(|frames| frames.iter().filter_map(|frame| frame["metrics"]["vmaf"].as_f64()).collect())
+            .unwrap_or_default())
+    }
+
+    /// Input arguments for the untrimmed input or a single trimmed segment
+    fn input_args(in_path: &str, segments: &[TrimSegment]) -> Vec<String> {
+        let mut args = Vec::new();
+        match segments {
+            [segment] => {
+                // Input seeking decodes from the previous keyframe and drops frames up to
+                // the start, so the cut is frame-accurate while transcoding
+                if segment.start > 0.0 {
+                    args.extend(["-ss".into(), format!("{:.3}", segment.start)]);
+                }
+                args.extend(["-i".into(), in_path.into()]);
+                if let Some(length) = segment.end.map(|end| end - segment.start) {
+                    args.extend(["-t".into(), format!("{:.3}", length)]);
+                }
+            }
+            _ => args.extend(["-i".into(), in_path.into()]),
+        }
+        args
+    }
+
+    /// Build a filter graph that cuts the segments out of the input and joins them
+    ///
+    /// Outputs `[v]` and, when `with_audio` is set, `[a]`.
+    fn concat_graph(
+        segments: &[TrimSegment],
+        input_duration: Option<f64>,
+        video_filter: Option<&str>,
+        audio_filter: Option<&str>,
+        with_audio: bool,
+    ) -> String {
+        let mut chains = Vec::new();
+        let mut inputs = String::new();
+
+        for (i, segment) in segments.iter().enumerate() {
+            let range = match segment.end.or(input_duration) {
+                Some(end) => format!("start={:.3}:end={:.3}", segment.start, end),
+                None => format!("start={:.3}", segment.start),
+            };
+            chains.push(format!("[0:V:0]trim={},setpts=PTS-STARTPTS[v{}]", range, i));
+            inputs.push_str(&format!("[v{}]", i));
+            if with_audio {
+                chains.push(format!("[0:a:0]atrim={},asetpts=PTS-STARTPTS[a{}]", range, i));
+                inputs.push_str(&format!("[a{}]", i));
+            }
+        }
+
+        let video_out = if video_filter.is_some() { "[joined]" } else { "[v]" };
+        let audio_filter = audio_filter.filter(|_| with_audio);
+        let audio_out = match (with_audio, audio_filter) {
+            (false, _) => "",
+            (true, Some(_)) => "[ajoined]",
+            (true, None) => "[a]",
+        };
+        chains.push(format!(
+            "{}concat=n={}:v=1:a={}{}{}",
+            inputs,
+            segments.len(),
+            u8::from(with_audio),
+            video_out,
+            audio_out
+        ));
+        if let Some(filter) = video_filter {
+            chains.push(format!("[joined]{}[v]", filter));
+        }
+        if let Some(filter) = audio_filter {
+            chains.push(format!("[ajoined]{}[a]", filter));
+        }
+
+        chains.join(";")
+    }
+
+    /// Pick the first encoder for the codec that this ffmpeg build provides
+    pub async fn resolve_encoder(format: VideoOutputFormat, codec: VideoCodec) -> Result<VideoEncoder, AppError> {
+        let candidates = format.encoders(codec);
+        let capabilities = require_ffmpeg().await?;
+
+        candidates
+            .iter()
+            .copied()
+            .find(|encoder| capabilities.has_encoder(encoder.ffmpeg_name()))
+            .ok_or_else(|| {
+                let names: Vec<&str> = candidates.iter().map(|e| e.ffmpeg_name()).collect();
+                let target = if format.is_animation() { format.name() } else { codec.name() };
+                AppError::EncoderUnavailable(ToolFailure {
+                    message: format!(
+                        "No encoder for {} is available in this ffmpeg build (tried {})",
+                        target,
+                        names.join(", ")
+                    ),
+                    diagnostic: None,
+                })
+            })
+    }
+
+    /// How long detected capabilities are reused before ffmpeg is probed again
+    const CAPABILITIES_TTL: Duration = Duration::from_secs(60);
+
+    /// Limit for each `-version`, `-encoders`, `-decoders` and `-filters` probe
+    const CAPABILITY_PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Last detected capabilities and when they were detected
+    static CAPABILITIES: std::sync::Mutex<Option<(Instant, Arc<FfmpegCapabilities>)>> = std::sync::Mutex::new(None);
+
+    /// Capabilities of the configured ffmpeg build, re-detected once `CAPABILITIES_TTL` has passed
+    ///
+    /// Installing or removing ffmpeg or one of its libraries is picked up without a restart.
+    /// Expired capabilities keep being served while a background task probes again, so only
+    /// the very first call waits for detection.
+    pub async fn capabilities() -> Arc<FfmpegCapabilities> {
+        static FIRST_DETECTION: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+        static REFRESHING: AtomicBool = AtomicBool::new(false);
+
+        if let Some((detected_at, capabilities)) = cached_capabilities() {
+            if detected_at.elapsed() >= CAPABILITIES_TTL && !REFRESHING.swap(true, Ordering::AcqRel) {
+                tokio::spawn(async {
+                    store_capabilities(detect_capabilities().await);
+                    REFRESHING.store(false, Ordering::Release);
+                });
+            }
+            return capabilities;
+        }
+
+        // Concurrent first calls wait for a single detection
+        let _detecting = FIRST_DETECTION.lock().await;
+        if let Some((_, capabilities)) = cached_capabilities() {
+            return capabilities;
+        }
+        store_capabilities(detect_capabilities().await)
+    }
+
+    fn cached_capabilities() -> Option<(Instant, Arc<FfmpegCapabilities>)> {
+        CAPABILITIES.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn store_capabilities(capabilities: FfmpegCapabilities) -> Arc<FfmpegCapabilities> {
+        let capabilities = Arc::new(capabilities);
+        *CAPABILITIES.lock().unwrap_or_else(|e| e.into_inner()) = Some((Instant::now(), capabilities.clone()));
+        capabilities
+    }
+
+    async fn detect_capabilities() -> FfmpegCapabilities {
+        let config = config::ffmpeg();
+        let (ffmpeg, ffprobe) = tokio::join!(probe_tool(&config.ffmpeg_path), probe_tool(&config.ffprobe_path));
+        let mut capabilities = FfmpegCapabilities { ffmpeg, ffprobe, ..FfmpegCapabilities::default() };
+        if capabilities.ffmpeg.available {
+            (capabilities.encoders, capabilities.decoders, capabilities.filters) = tokio::join!(
+                list_components("-encoders", FfmpegCapabilities::ENCODERS),
+                list_components("-decoders", FfmpegCapabilities::DECODERS),
+                list_components("-filters", FfmpegCapabilities::FILTERS),
+            );
+        }
+        capabilities
+    }
+
+    /// Run `<tool> -version` to check the tool works and read its version
+    async fn probe_tool(path: &str) -> ToolInfo {
+        let mut command = Command::new(path);
+        command.arg("-version");
+        let unavailable = ToolInfo { path: path.to_string(), available: false, version: None };
+        match output_with_limits(command, CAPABILITY_PROBE_TIMEOUT, None).await {
+            Ok(output) if output.status.success() => {
+                // The first line looks like "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) ..."
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let version = stdout.lines().next().and_then(|line| line.split_whitespace().nth(2)).map(str::to_string);
+                ToolInfo { available: true, version, ..unavailable }
+            }
+            Ok(output) => {
+                tracing::warn!("{} -version exited with {}", path, output.status);
+                unavailable
+            }
+            Err(e) => {
+                tracing::warn!("{} is not usable: {}", path, e);
+                unavailable
+            }
+        }
+    }
+
+    /// Names from `ffmpeg -encoders`, `-decoders` or `-filters` that are in `wanted`
+    async fn list_components(flag: &str, wanted: &[&str]) -> Vec<String> {
+        let mut command = Command::new(&config::ffmpeg().ffmpeg_path);
+        command.args(["-hide_banner", flag]);
+        let output = match output_with_limits(command, CAPABILITY_PROBE_TIMEOUT, None).await {
+            Ok(output) if output.status.success() => output,
+            _ => {
+                tracing::warn!("Failed to list ffmpeg {}", flag.trim_start_matches('-'));
+                return Vec::new();
+            }
+        };
+
+        // Lines look like " V....D libvpx-vp9   libvpx VP9 (codec vp9)" or " TSC scale   V->V   Scale the input video"
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let _flags = parts.next()?;
+                let name = parts.next()?;
+                wanted.contains(&name).then(|| name.to_string())
+            })
+            .collect()
+    }
+
+    /// Fail with `501 FFMPEG_UNAVAILABLE` when ffmpeg or ffprobe cannot be run
+    pub async fn require_ffmpeg() -> Result<Arc<FfmpegCapabilities>, AppError> {
+        let capabilities = capabilities().await;
+        for tool in [&capabilities.ffmpeg, &capabilities.ffprobe] {
+            if !tool.available {
+                return Err(AppError::FfmpegUnavailable(format!(
+                    "Video processing is unavailable: '{}' could not be run on this server",
+                    tool.path
+                )));
+            }
+        }
+        Ok(capabilities)
+    }
+
+    /// Fail with `501 FFMPEG_UNAVAILABLE` when ffprobe cannot be run
+    pub async fn require_ffprobe() -> Result<(), AppError> {
+        let capabilities = capabilities().await;
+        let ffprobe = &capabilities.ffprobe;
+        if ffprobe.available {
+            return Ok(());
+        }
+        Err(AppError::FfmpegUnavailable(format!(
+            "Video inspection is unavailable: '{}' could not be run on this server",
+            ffprobe.path
+        )))
+    }
+
+    /// Fail with `501 ENCODER_UNAVAILABLE` when the ffmpeg build lacks an encoder
+    fn require_encoder(capabilities: &FfmpegCapabilities, encoder: &str, purpose: &str) -> Result<(), AppError> {
+        if capabilities.has_encoder(encoder) {
+            return Ok(());
+        }
+        Err(AppError::EncoderUnavailable(ToolFailure {
+            message: format!("{} requires the {} encoder, which this ffmpeg build lacks", purpose, encoder),
+            diagnostic: None,
+        }))
+    }
+
+    /// Fail with `415 UNSUPPORTED_CODEC` when the ffmpeg build cannot decode a stream that gets converted
+    fn require_decoders<'a>(
+        capabilities: &FfmpegCapabilities,
+        streams: impl IntoIterator<Item = &'a ProbeStream>,
+    ) -> Result<(), AppError> {
+        let missing: Vec<&str> = streams
+            .into_iter()
+            .filter_map(|stream| stream.codec_name.as_deref())
+            .filter(|codec| !capabilities.can_decode(codec))
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(AppError::UnsupportedCodec(ToolFailure {
+            message: format!("This ffmpeg build has no decoder for the input's {} stream", missing.join(" and ")),
+            diagnostic: None,
+        }))
+    }
+
+    /// Fail with `501 ENCODER_UNAVAILABLE` when the ffmpeg build lacks a filter
+    fn require_filter(capabilities: &FfmpegCapabilities, filter: &str, purpose: &str) -> Result<(), AppError> {
+        if capabilities.has_filter(filter) {
+            return Ok(());
+        }
+        Err(AppError::EncoderUnavailable(ToolFailure {
+            message: format!("{} requires the {} filter, which this ffmpeg build lacks", purpose, filter),
+            diagnostic: None,
+        }))
+    }
+
+    /// Run ffmpeg with machine-readable progress on stdout and wait for it to finish
+    ///
+    /// `pass` is the zero-based index and total number of ffmpeg runs of the job,
+    /// used to report overall rather than per-run progress.
+    async fn run_ffmpeg(
+        args: &[String],
+        duration: Option<f64>,
+        job: Option<&JobHandle>,
+        pass: (usize, usize),
+    ) -> Result<(), AppError> {
+        if job.is_some_and(|job| job.is_cancelled()) {
+            return Err(AppError::Cancelled("Job was cancelled".to_string()));
+        }
+
+        // kill_on_drop stops ffmpeg when the request future is dropped, e.g. on client disconnect
+        let mut child = Command::new(&config::ffmpeg().ffmpeg_path)
+            .args(["-hide_banner", "-nostats", "-progress", "pipe:1"])
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| AppError::ProcessingError(format!("Failed to execute ffmpeg: {}", e)))?;
+
+        // Drained concurrently so a chatty ffmpeg never blocks on a full stderr pipe
+        let stderr_tail = child.stderr.take().map(|stderr| {
+            tokio::spawn(async move {
+                let mut tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if tail.len() == STDERR_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line);
+                }
+                Vec::from(tail)
+            })
+        });
+
+        let stdout = child.stdout.take();
+        let run = async {
+            if let Some(stdout) = stdout {
+                let mut lines = BufReader::new(stdout).lines();
+                let mut progress = match job {
+                    Some(job) => job.snapshot(),
+                    None => JobProgress::new(String::new(), crate::models::JobStatus::Running),
+                };
+                progress.duration_secs = duration;
+                if let Some(job) = job {
+                    job.update(progress.clone());
+                }
+
+                let (index, count) = (pass.0 as f64, pass.1.max(1) as f64);
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if apply_progress_line(&mut progress, &line) {
+                        tracing::debug!("📊 ffmpeg progress: {:?}% (speed {:?}x)", progress.percent, progress.speed);
+                        if let Some(job) = job {
+                            let mut overall = progress.clone();
+                            overall.percent = progress.percent.map(|p| (index * 100.0 + p) / count);
+                            // Remaining runs take roughly as long as a full run at the current speed
+                            let remaining_runs = count - index - 1.0;
+                            overall.eta_secs = progress.eta_secs.map(|eta| {
+                                let per_run = duration.zip(progress.speed).map(|(d, s)| d / s).unwrap_or(0.0);
+                                eta + remaining_runs * per_run
+                            });
+                            job.update(overall);
+                        }
+                    }
+                }
+            }
+            child.wait().await
+        };
+
+        let timeout = config::ffmpeg().timeout_for(duration);
+        let result = tokio::select! {
+            status = run => status.map_err(|e| AppError::ProcessingError(format!("Failed to wait for ffmpeg: {}", e))),
+            _ = tokio::time::sleep(timeout) => Err(AppError::Timeout(format!(
+                "ffmpeg did not finish within {} seconds", timeout.as_secs()
+            ))),
+            _ = wait_cancelled(job) => Err(AppError::Cancelled("Job was cancelled".to_string())),
+        };
+        let status = match result {
+            Ok(status) => status,
+            Err(e) => {
+                tracing::warn!("🛑 Stopping ffmpeg: {}", e);
+                if let Err(kill_error) = child.kill().await {
+                    tracing::error!("Failed to kill ffmpeg: {}", kill_error);
+                }
+                return Err(e);
+            }
+        };
+
+        if !status.success() {
+            let tail = match stderr_tail {
+                Some(task) => task.await.unwrap_or_default(),
+                None => Vec::new(),
+            };
+            tracing::error!("ffmpeg exited with status {}:\n{}", status, tail.join("\n"));
+            return Err(classify_ffmpeg_failure(&status.to_string(), &tail));
+        }
+
+        Ok(())
+    }
+
+    /// Resolve when the job is cancelled, never for runs without a job
+    async fn wait_cancelled(job: Option<&JobHandle>) {
+        match job {
+            Some(job) => job.cancelled().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Run a command to completion, killing it on timeout or job cancellation
+    async fn output_with_limits(
+        mut command: Command,
+        timeout: Duration,
+        job: Option<&JobHandle>,
+    ) -> Result<std::process::Output, AppError> {
+        let program = command.as_std().get_program().to_string_lossy().into_owned();
+        // Dropping the output future kills the child
+        let output = command.stdin(Stdio::null()).kill_on_drop(true).output();
+        tokio::select! {
+            output = output => output.map_err(|e| AppError::ProcessingError(format!("Failed to execute {}: {}", program, e))),
+            _ = tokio::time::sleep(timeout) => Err(AppError::Timeout(format!(
+                "{} did not finish within {} seconds", program, timeout.as_secs()
+            ))),
+            _ = wait_cancelled(job) => Err(AppError::Cancelled("Job was cancelled".to_string())),
+        }
+    }
+
+    /// Map the stderr tail of a failed ffmpeg run to a specific error
+    fn classify_ffmpeg_failure(status: &str, tail: &[String]) -> AppError {
+        let log = tail.join("\n").to_lowercase();
+        let has = |patterns: &[&str]| patterns.iter().any(|p| log.contains(p));
+        let failure = |message: &str| ToolFailure { message: message.to_string(), diagnostic: sanitize_diagnostic(tail) };
+
+        if has(&["no space left on device", "disk quota exceeded"]) {
+            AppError::InsufficientStorage(failure("The server ran out of disk space while converting"))
+        } else if has(&["unknown encoder", "encoder not found", "requested encoder"]) {
+            AppError::EncoderUnavailable(failure("The required encoder is not available in this ffmpeg build"))
+        } else if has(&["decoder not found", "unsupported codec", "could not find codec parameters", "no decoder for"]) {
+            AppError::UnsupportedCodec(failure("The input uses a codec that cannot be decoded"))
+        } else if has(&["does not contain any stream", "matches no streams", "output file is empty"]) {
+            AppError::NoStreams(failure("The input has no streams that can be converted"))
+        } else if has(&["invalid data found", "moov atom not found", "error while decoding"]) {
+            AppError::InvalidMedia(failure("The input is corrupt or not a valid media file"))
+        } else {
+            let message = format!("ffmpeg exited with {}", status);
+            AppError::FfmpegFailed(failure(&message))
+        }
+    }
+
+    /// Keep the last meaningful stderr lines, without temp paths or memory addresses
+    fn sanitize_diagnostic(tail: &[String]) -> Option<String> {
+        let temp_dir = std::env::temp_dir().to_string_lossy().trim_end_matches('/').to_string();
+        let mut lines: Vec<String> = tail
+            .iter()
+            .rev()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .take(DIAGNOSTIC_LINES)
+            .map(|line| {
+                let line: String = line.chars().filter(|c| !c.is_control()).take(DIAGNOSTIC_LINE_CHARS).collect();
+                line.split(' ').map(|word| sanitize_word(word, &temp_dir)).collect::<Vec<_>>().join(" ")
+            })
+            .collect();
+        lines.reverse();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    fn sanitize_word(word: &str, temp_dir: &str) -> String {
+        if let Some(start) = word.find(temp_dir) {
+            let path = &word[start..];
+            let end = path.trim_end_matches([':', '\'', '"', ',', ')', ']']).len();
+            return format!("{}<file>{}", &word[..start], &path[end..]);
+        }
+        let address = word.trim_end_matches(']');
+        if address.len() > 2 && address.starts_with("0x") && address[2..].chars().all(|c| c.is_ascii_hexdigit()) {
+            return format!("<addr>{}", &word[address.len()..]);
+        }
+        word.to_string()
+    }
+
+    /// Fold one `key=value` line of ffmpeg's `-progress` output into the snapshot.
+    ///
+    /// Returns true when the line closes a progress block and the snapshot should be published.
+    pub fn apply_progress_line(progress: &mut JobProgress, line: &str) -> bool {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            return false;
+        };
+        let value = value.trim();
+
+        match key {
+            // Despite the name, out_time_ms is reported in microseconds as well
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<i64>() {
+                    progress.out_time_secs = us.max(0) as f64 / 1_000_000.0;
+                }
+            }
+            "fps" => progress.fps = value.parse().ok(),
+            "speed" => progress.speed = value.trim_end_matches('x').trim().parse().ok(),
+            "bitrate" => progress.bitrate_kbps = value.trim_end_matches("kbits/s").trim().parse().ok(),
+            "progress" => {
+                if let Some(duration) = progress.duration_secs.filter(|d| *d > 0.0) {
+                    let percent = if value == "end" { 100.0 } else { progress.out_time_secs / duration * 100.0 };
+                    progress.percent = Some(percent.clamp(0.0, 100.0));
+                    progress.eta_secs = progress
+                        .speed
+                        .filter(|speed| *speed > 0.0)
+                        .map(|speed| ((duration - progress.out_time_secs) / speed).max(0.0));
+                }
+                return true;
+            }
+            _ => {}
+        }
+
+        false
+    }
+
+    /// Video codecs accepted as input
+    pub const ALLOWED_VIDEO_CODECS: &[&str] = &[
+        "h264", "hevc", "mpeg4", "mpeg2video", "mjpeg", "prores", "vp8", "vp9", "av1", "gif",
+    ];
+
+    /// Audio codecs accepted as input
+    pub const ALLOWED_AUDIO_CODECS: &[&str] = &[
+        "aac", "mp3", "opus", "vorbis", "flac", "alac", "ac3", "eac3",
+        "pcm_s16le", "pcm_s16be", "pcm_s24le", "pcm_s32le", "pcm_f32le", "pcm_u8",
+    ];
+
+    /// Detect the input container from its magic bytes
+    pub fn sniff_container(data: &[u8]) -> Option<VideoContainer> {
+        if data.len() < 12 {
+            return None;
+        }
+
+        // ISO base media (MP4/MOV): size + "ftyp" + major brand
+        if &data[4..8] == b"ftyp" {
+            return Some(if &data[8..12] == b"qt  " { VideoContainer::Mov } else { VideoContainer::Mp4 });
+        }
+        // Older QuickTime files start directly with other top-level atoms
+        if [b"moov", b"mdat", b"wide", b"free", b"skip", b"pnot"].iter().any(|atom| &data[4..8] == *atom) {
+            return Some(VideoContainer::Mov);
+        }
+        // EBML header, the DocType tells WebM and Matroska apart
+        if data[0..4] == [0x1A, 0x45, 0xDF, 0xA3] {
+            let header = &data[..data.len().min(64)];
+            let is_webm = header.windows(4).any(|w| w == b"webm");
+            return Some(if is_webm { VideoContainer::WebM } else { VideoContainer::Mkv });
+        }
+        if &data[0..4] == b"RIFF" && &data[8..12] == b"AVI " {
+            return Some(VideoContainer::Avi);
+        }
+        if &data[0..6] == b"GIF87a" || &data[0..6] == b"GIF89a" {
+            return Some(VideoContainer::Gif);
+        }
+
+        None
+    }
+
+    /// Detect a plain audio container from its magic bytes
+    pub fn sniff_audio_container(data: &[u8]) -> Option<AudioContainer> {
+        if data.len() < 12 {
+            return None;
+        }
+
+        if &data[0..4] == b"RIFF" && &data[8..12] == b"WAVE" {
+            return Some(AudioContainer::Wav);
+        }
+        if &data[0..4] == b"fLaC" {
+            return Some(AudioContainer::Flac);
+        }
+        // ID3v2 tag, or a bare MPEG audio frame sync; layer bits 00 mark AAC in ADTS instead
+        if &data[0..3] == b"ID3" || (data[0] == 0xFF && data[1] & 0xE0 == 0xE0 && data[1] & 0x06 != 0) {
+            return Some(AudioContainer::Mp3);
+        }
+
+        None
+    }
+
+    /// Run ffprobe on a file and parse its JSON output
+    pub async fn probe_media(path: &str) -> Result<ProbeOutput, AppError> {
+        let mut command = Command::new(&config::ffmpeg().ffprobe_path);
+        command.args(["-v", "error", "-show_format", "-show_streams", "-of", "json"]).arg(path);
+        let output = output_with_limits(command, config::ffmpeg().timeout_for(None), None).await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::warn!("ffprobe rejected input: {}", stderr.trim());
+            return Err(AppError::BadRequest("Input is not a readable media file".to_string()));
+        }
+
+        serde_json::from_slice(&output.stdout)
+            .map_err(|e| AppError::ProcessingError(format!("Failed to parse ffprobe output: {}", e)))
+    }
+
+    /// Check that the input of the audio pipeline has a supported audio track
+    pub fn validate_audio_probe(source: AudioSource, probe: &ProbeOutput) -> Result<(), AppError> {
+        let container = match source {
+            AudioSource::Video(container) => return validate_probe(container, probe).and_then(|_| require_audio(source, probe)),
+            AudioSource::Audio(container) => container,
+        };
+
+        let format_name = probe.format.as_ref().and_then(|f| f.format_name.clone()).unwrap_or_default();
+        if !format_name.split(',').any(|f| f == container.ffprobe_format()) {
+            return Err(AppError::BadRequest(format!(
+                "Input looks like {} but ffprobe detected format '{}'",
+                container.name(),
+                format_name
+            )));
+        }
+
+        require_audio(source, probe)?;
+        let unsupported: Vec<&str> = probe
+            .streams_of("audio")
+            .filter_map(|s| s.codec_name.as_deref())
+            .filter(|codec| !ALLOWED_AUDIO_CODECS.contains(codec))
+            .collect();
+        if !unsupported.is_empty() {
+            return Err(AppError::BadRequest(format!(
+                "Unsupported codec(s) in {} input: {} (supported audio: {})",
+                container.name(),
+                unsupported.join(", "),
+                ALLOWED_AUDIO_CODECS.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn require_audio(source: AudioSource, probe: &ProbeOutput) -> Result<(), AppError> {
+        if probe.streams_of("audio").next().is_none() {
+            return Err(AppError::BadRequest(format!("{} input contains no audio stream", source.name())));
+        }
+        Ok(())
+    }
+
+    /// Check the probed streams against the sniffed container and the codec allow-lists
+    pub fn validate_probe(container: VideoContainer, probe: &ProbeOutput) -> Result<(), AppError> {
+        let format_name = probe.format.as_ref().and_then(|f| f.format_name.clone()).unwrap_or_default();
+        if !format_name.split(',').any(|f| f == container.ffprobe_format()) {
+            return Err(AppError::BadRequest(format!(
+                "Input looks like {} but ffprobe detected format '{}'",
+                container.name(),
+                format_name
+            )));
+        }
+
+        let video_codecs: Vec<&str> = probe.streams_of("video").filter_map(|s| s.codec_name.as_deref()).collect();
+        if video_codecs.is_empty() {
+            return Err(AppError::BadRequest(format!("{} input contains no video stream", container.name())));
+        }
+
+        let unsupported: Vec<&str> = video_codecs
+            .iter()
+            .copied()
+            .filter(|codec| !ALLOWED_VIDEO_CODECS.contains(codec))
+            .chain(
+                probe
+                    .streams_of("audio")
+                    .filter_map(|s| s.codec_name.as_deref())
+                    .filter(|codec| !ALLOWED_AUDIO_CODECS.contains(codec)),
+            )
+            .collect();
+        if !unsupported.is_empty() {
+            return Err(AppError::BadRequest(format!(
+                "Unsupported codec(s) in {} input: {} (supported video: {}; audio: {})",
+                container.name(),
+                unsupported.join(", "),
+                ALLOWED_VIDEO_CODECS.join(", "),
+                ALLOWED_AUDIO_CODECS.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::models::JobStatus;
+
+        fn padded(head: &[u8]) -> Vec<u8> {
+            let mut data = head.to_vec();
+            data.resize(16, 0);
+            data
+        }
+
+        #[test]
+        fn sniffs_video_containers() {
+            assert_eq!(sniff_container(&padded(b"\0\0\0\x20ftypisom")), Some(VideoContainer::Mp4));
+            assert_eq!(sniff_container(&padded(b"\0\0\0\x14ftypqt  ")), Some(VideoContainer::Mov));
+            assert_eq!(sniff_container(&padded(b"\0\0\0\x08wide")), Some(VideoContainer::Mov));
+            assert_eq!(sniff_container(&padded(b"\x1A\x45\xDF\xA3\x42\x82\x84webm")), Some(VideoContainer::WebM));
+            assert_eq!(sniff_container(&padded(b"\x1A\x45\xDF\xA3\x42\x82\x88matroska")), Some(VideoContainer::Mkv));
+            assert_eq!(sniff_container(&padded(b"RIFF\0\0\0\0AVI ")), Some(VideoContainer::Avi));
+            assert_eq!(sniff_container(&padded(b"GIF89a")), Some(VideoContainer::Gif));
+            assert_eq!(sniff_container(&padded(b"\x89PNG\r\n\x1a\n")), None);
+            assert_eq!(sniff_container(b"\0\0\0\x20ftyp"), None);
+        }
+
+        #[test]
+        fn sniffs_audio_containers() {
+            assert_eq!(sniff_audio_container(&padded(b"RIFF\0\0\0\0WAVE")), Some(AudioContainer::Wav));
+            assert_eq!(sniff_audio_container(&padded(b"fLaC")), Some(AudioContainer::Flac));
+            assert_eq!(sniff_audio_container(&padded(b"ID3\x04")), Some(AudioContainer::Mp3));
+            // MPEG-1 layer III frame sync
+            assert_eq!(sniff_audio_container(&padded(&[0xFF, 0xFB, 0x90])), Some(AudioContainer::Mp3));
+            assert_eq!(sniff_audio_container(&padded(b"RIFF\0\0\0\0AVI ")), None);
+        }
+
+        #[test]
+        fn concat_graph_joins_segments() {
+            let segments = [TrimSegment { start: 1.0, end: Some(2.5) }, TrimSegment { start: 4.0, end: None }];
+            assert_eq!(
+                concat_graph(&segments, Some(6.0), None, None, true),
+                "[0:V:0]trim=start=1.000:end=2.500,setpts=PTS-STARTPTS[v0];\
+                 [0:a:0]atrim=start=1.000:end=2.500,asetpts=PTS-STARTPTS[a0];\
+                 [0:V:0]trim=start=4.000:end=6.000,setpts=PTS-STARTPTS[v1];\
+                 [0:a:0]atrim=start=4.000:end=6.000,asetpts=PTS-STARTPTS[a1];\
+                 [v0][a0][v1][a1]concat=n=2:v=1:a=1[v][a]"
+            );
+        }
+
+        #[test]
+        fn concat_graph_applies_filters_after_joining() {
+            let segments = [TrimSegment { start: 0.0, end: Some(1.0) }, TrimSegment { start: 2.0, end: None }];
+            assert_eq!(
+                concat_graph(&segments, None, Some("fps=15"), Some("loudnorm"), false),
+                "[0:V:0]trim=start=0.000:end=1.000,setpts=PTS-STARTPTS[v0];\
+                 [0:V:0]trim=start=2.000,setpts=PTS-STARTPTS[v1];\
+                 [v0][v1]concat=n=2:v=1:a=0[joined];\
+                 [joined]fps=15[v]"
+            );
+            assert!(concat_graph(&segments, None, None, Some("loudnorm"), true).ends_with("[ajoined];[ajoined]loudnorm[a]"));
+        }
+
+        #[test]
+        fn validates_segments_against_the_input() {
+            let segments = [TrimSegment { start: 1.0, end: Some(3.0) }, TrimSegment { start: 5.0, end: None }];
+            assert_eq!(validate_segments(&segments, Some(10.0)).unwrap(), Some(7.0));
+            assert_eq!(validate_segments(&[], Some(10.0)).unwrap(), Some(10.0));
+            assert_eq!(validate_segments(&segments, None).unwrap(), None);
+            assert!(validate_segments(&segments, Some(4.0)).is_err());
+            assert!(validate_segments(&[TrimSegment { start: 1.0, end: Some(12.0) }], Some(10.0)).is_err());
+        }
+
+        fn tail(lines: &[&str]) -> Vec<String> {
+            lines.iter().map(|line| line.to_string()).collect()
+        }
+
+        #[test]
+        fn classifies_ffmpeg_failures() {
+            let classify = |line: &str| classify_ffmpeg_failure("exit status: 1", &tail(&["Input #0, mov", line]));
+            assert!(matches!(classify("Error writing trailer: No space left on device"), AppError::InsufficientStorage(_)));
+            assert!(matches!(classify("Unknown encoder 'libsvtav1'"), AppError::EncoderUnavailable(_)));
+            assert!(matches!(classify("Decoder not found for stream #0:0"), AppError::UnsupportedCodec(_)));
+            assert!(matches!(classify("Stream map '0:V:0' matches no streams."), AppError::NoStreams(_)));
+            assert!(matches!(classify("moov atom not found"), AppError::InvalidMedia(_)));
+            // Too generic to blame the input for
+            assert!(matches!(classify("Error parsing options: Invalid argument"), AppError::FfmpegFailed(_)));
+            assert!(matches!(classify("Error while filtering: End of file"), AppError::FfmpegFailed(_)));
+
+            let AppError::FfmpegFailed(failure) = classify_ffmpeg_failure("signal: 9", &[]) else {
+                panic!("expected FfmpegFailed");
+            };
+            assert_eq!(failure.message, "ffmpeg exited with signal: 9");
+            assert_eq!(failure.diagnostic, None);
+        }
+
+        #[test]
+        fn sanitizes_diagnostics() {
+            let temp_dir = std::env::temp_dir().to_string_lossy().trim_end_matches('/').to_string();
+            let lines = tail(&[
+                "first",
+                "",
+                &format!("[mov,mp4 @ 0x55d0c1a2b3c0] moov atom not found in '{}/.tmpAbC123':", temp_dir),
+                &format!("{}/.tmpAbC123: Invalid data found when processing input", temp_dir),
+                "bell\x07",
+            ]);
+            let diagnostic = sanitize_diagnostic(&lines).unwrap();
+            assert_eq!(
+                diagnostic,
+                "first\n\
+                 [mov,mp4 @ <addr>] moov atom not found in '<file>':\n\
+                 <file>: Invalid data found when processing input\n\
+                 bell"
+            );
+
+            let long = "x".repeat(DIAGNOSTIC_LINE_CHARS * 2);
+            assert_eq!(sanitize_diagnostic(&tail(&[&long])).unwrap().len(), DIAGNOSTIC_LINE_CHARS);
+            let many: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+            assert_eq!(sanitize_diagnostic(&many).unwrap(), "5\n6\n7\n8\n9");
+            assert_eq!(sanitize_diagnostic(&tail(&["", "  "])), None);
+        }
+
+        #[test]
+        fn folds_progress_lines() {
+            let mut progress = JobProgress::new("job".to_string(), JobStatus::Running);
+            progress.duration_secs = Some(10.0);
+
+            for line in ["frame=100", "fps=25.0", "bitrate= 512.3kbits/s", "out_time_us=4000000", "speed=2x"] {
+                assert!(!apply_progress_line(&mut progress, line));
+            }
+            assert!(apply_progress_line(&mut progress, "progress=continue"));
+            assert_eq!(progress.out_time_secs, 4.0);
+            assert_eq!(progress.fps, Some(25.0));
+            assert_eq!(progress.bitrate_kbps, Some(512.3));
+            assert_eq!(progress.percent, Some(40.0));
+            assert_eq!(progress.eta_secs, Some(3.0));
+
+            // Negative timestamps before the first frame and unknown values are tolerated
+            assert!(!apply_progress_line(&mut progress, "out_time_ms=-5"));
+            assert!(!apply_progress_line(&mut progress, "speed=N/A"));
+            assert!(!apply_progress_line(&mut progress, "garbage"));
+            assert_eq!(progress.out_time_secs, 0.0);
+            assert_eq!(progress.speed, None);
+
+            assert!(apply_progress_line(&mut progress, "progress=end"));
+            assert_eq!(progress.percent, Some(100.0));
+            assert_eq!(progress.eta_secs, None);
+        }
+
+        #[test]
+        fn adts_is_not_mp3() {
+            assert_eq!(sniff_audio_container(&padded(&[0xFF, 0xF1, 0x50, 0x80])), None);
+            assert_eq!(sniff_audio_container(&padded(&[0xFF, 0xF9, 0x50, 0x80])), None);
+        }
+    }
+}
+
+pub mod jobs {
+    use crate::errors::AppError;
+    use crate::models::{JobProgress, JobStatus};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tokio::sync::watch;
+
+    /// How long a finished job stays available to late subscribers
+    const FINISHED_JOB_TTL: Duration = Duration::from_secs(60);
+
+    /// Jobs without any update for this long are purged
+    const STALE_JOB_TTL: Duration = Duration::from_secs(30 * 60);
+
+    /// Subscriptions to jobs that never start are purged, and their streams closed, after this long
+    pub const PENDING_JOB_TTL: Duration = Duration::from_secs(5 * 60);
+
+    /// Most subscriptions that may wait for their job to start
+    const MAX_PENDING_JOBS: usize = 1000;
+
+    /// Seconds a client should wait before subscribing again when too many jobs are pending
+    const PENDING_RETRY_AFTER_SECS: u64 = 30;
+
+    struct JobEntry {
+        sender: watch::Sender<JobProgress>,
+        /// Set to true to ask the running conversion to stop
+        cancel: watch::Sender<bool>,
+        updated_at: Instant,
+    }
+
+    impl JobEntry {
+        fn new(progress: JobProgress) -> Self {
+            let (sender, _) = watch::channel(progress);
+            let (cancel, _) = watch::channel(false);
+            Self { sender, cancel, updated_at: Instant::now() }
+        }
+
+        fn is_pending(&self) -> bool {
+            self.sender.borrow().status == JobStatus::Pending
+        }
+    }
+
+    /// Check a client-chosen job id
+    fn validate_id(job_id: &str) -> Result<(), AppError> {
+        let valid = !job_id.is_empty()
+            && job_id.len() <= 64
+            && job_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(AppError::BadRequest(
+                "job_id must be 1-64 characters of letters, digits, '-' or '_'".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Drop jobs that stopped updating and subscriptions whose job never started
+    fn purge(jobs: &mut HashMap<String, JobEntry>) {
+        jobs.retain(|_, entry| {
+            let ttl = if entry.is_pending() { PENDING_JOB_TTL } else { STALE_JOB_TTL };
+            entry.updated_at.elapsed() < ttl
+        });
+    }
+
+    /// Registry of progress channels keyed by job id
+    #[derive(Clone, Default)]
+    pub struct JobRegistry {
+        jobs: Arc<Mutex<HashMap<String, JobEntry>>>,
+    }
+
+    impl JobRegistry {
+        /// Register a running job, reusing the channel of clients that subscribed early
+        pub fn start(&self, job_id: String) -> Result<JobHandle, AppError> {
+            validate_id(&job_id)?;
+
+            let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            purge(&mut jobs);
+
+            let progress = JobProgress::new(job_id.clone(), JobStatus::Running);
+            let cancelled = match jobs.get_mut(&job_id) {
+                Some(entry) if entry.sender.borrow().status != JobStatus::Pending => {
+                    return Err(AppError::BadRequest(format!("Job id '{}' is already in use", job_id)));
+                }
+                Some(entry) => {
+                    entry.sender.send_replace(progress);
+                    entry.cancel.send_replace(false);
+                    entry.updated_at = Instant::now();
+                    entry.cancel.subscribe()
+                }
+                None => {
+                    let entry = JobEntry::new(progress);
+                    let cancelled = entry.cancel.subscribe();
+                    jobs.insert(job_id.clone(), entry);
+                    cancelled
+                }
+            };
+
+            tracing::info!("🆔 Started job {}", job_id);
+            Ok(JobHandle { registry: self.clone(), job_id, cancelled, finished: false })
+        }
+
+        /// Ask a running job to stop; its ffmpeg process is killed and its temp files removed
+        pub fn cancel(&self, job_id: &str) -> Result<(), AppError> {
+            let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            let entry = jobs.get(job_id).ok_or_else(|| AppError::NotFound(format!("Job '{}' not found", job_id)))?;
+
+            let status = entry.sender.borrow().status;
+            if !matches!(status, JobStatus::Queued | JobStatus::Running) {
+                return Err(AppError::BadRequest(format!("Job '{}' is not running", job_id)));
+            }
+            entry.cancel.send_replace(true);
+            tracing::info!("🛑 Cancellation requested for job {}", job_id);
+            Ok(())
+        }
+
+        /// Subscribe to progress updates, creating a pending entry if the job has not started yet
+        ///
+        /// Pending entries are limited to `MAX_PENDING_JOBS` and purged after
+        /// `PENDING_JOB_TTL` when no conversion starts with their id.
+        pub fn subscribe(&self, job_id: &str) -> Result<watch::Receiver<JobProgress>, AppError> {
+            validate_id(job_id)?;
+
+            let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            purge(&mut jobs);
+            if let Some(entry) = jobs.get(job_id) {
+                return Ok(entry.sender.subscribe());
+            }
+
+            if jobs.values().filter(|entry| entry.is_pending()).count() >= MAX_PENDING_JOBS {
+                return Err(AppError::ServerBusy {
+                    message: "Too many subscriptions are waiting for their job to start".to_string(),
+                    retry_after_secs: PENDING_RETRY_AFTER_SECS,
+                });
+            }
+            let entry = JobEntry::new(JobProgress::new(job_id.to_string(), JobStatus::Pending));
+            let receiver = entry.sender.subscribe();
+            jobs.insert(job_id.to_string(), entry);
+            Ok(receiver)
+        }
+
+        fn publish(&self, job_id: &str, progress: JobProgress) {
+            let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(entry) = jobs.get_mut(job_id) {
+                entry.sender.send_replace(progress);
+                entry.updated_at = Instant::now();
+            }
+        }
+
+        fn snapshot(&self, job_id: &str) -> Option<JobProgress> {
+            let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            jobs.get(job_id).map(|entry| entry.sender.borrow().clone())
+        }
+
+        /// Drop a finished job after the grace period so late subscribers still see the outcome
+        fn remove_later(&self, job_id: String) {
+            let registry = self.clone();
+            let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+                return;
+            };
+            runtime.spawn(async move {
+                tokio::time::sleep(FINISHED_JOB_TTL).await;
+                let mut jobs = registry.jobs.lock().unwrap_or_else(|e| e.into_inner());
+                if jobs.get(&job_id).is_some_and(|entry| entry.sender.borrow().status.is_terminal()) {
+                    jobs.remove(&job_id);
+                }
+            });
+        }
+    }
+
+    /// Handle used by a running conversion to publish its progress
+    ///
+    /// Dropping the handle without calling `complete` marks the job as
+    /// cancelled, which happens when the client aborts the request.
+    pub struct JobHandle {
+        registry: JobRegistry,
+        job_id: String,
+        cancelled: watch::Receiver<bool>,
+        finished: bool,
+    }
+
+    impl JobHandle {
+        pub fn id(&self) -> &str {
+            &self.job_id
+        }
+
+        /// Current progress snapshot of this job
+        pub fn snapshot(&self) -> JobProgress {
+            self.registry
+                .snapshot(&self.job_id)
+                .unwrap_or_else(|| JobProgress::new(self.job_id.clone(), JobStatus::Running))
+        }
+
+        /// Publish a progress update
+        pub fn update(&self, mut progress: JobProgress) {
+            progress.job_id = self.job_id.clone();
+            progress.status = JobStatus::Running;
+            progress.queue_position = None;
+            self.registry.publish(&self.job_id, progress);
+        }
+
+        /// Publish that the job left the queue and got a worker
+        pub fn running(&self) {
+            self.update(self.snapshot());
+        }
+
+        /// Publish the job's position in the worker queue
+        pub fn queued(&self, position: usize) {
+            let mut progress = self.snapshot();
+            progress.status = JobStatus::Queued;
+            progress.queue_position = Some(position);
+            self.registry.publish(&self.job_id, progress);
+        }
+
+        /// Whether cancellation of this job was requested
+        pub fn is_cancelled(&self) -> bool {
+            *self.cancelled.borrow()
+        }
+
+        /// Resolve once cancellation of this job is requested
+        pub async fn cancelled(&self) {
+            let mut cancelled = self.cancelled.clone();
+            if cancelled.wait_for(|cancelled| *cancelled).await.is_err() {
+                // The registry entry is gone, so nobody can cancel anymore
+                std::future::pending::<()>().await;
+            }
+        }
+
+        pub fn complete(mut self) {
+            self.finish(JobStatus::Completed, None);
+        }
+
+        /// Finish with the error that stopped the conversion
+        pub fn fail(mut self, error: &AppError) {
+            let status = match error {
+                AppError::Cancelled(_) => JobStatus::Cancelled,
+                _ => JobStatus::Failed,
+            };
+            self.finish(status, Some(error.to_string()));
+        }
+
+        fn finish(&mut self, status: JobStatus, message: Option<String>) {
+            if self.finished {
+                return;
+            }
+            self.finished = true;
+
+            let mut progress = self.snapshot();
+            progress.status = status;
+            progress.message = message;
+            if status == JobStatus::Completed {
+                progress.percent = Some(100.0);
+                progress.eta_secs = Some(0.0);
+            }
+
+            self.registry.publish(&self.job_id, progress);
+            self.registry.remove_later(self.job_id.clone());
+        }
+    }
+
+    impl Drop for JobHandle {
+        fn drop(&mut self) {
+            self.finish(JobStatus::Cancelled, Some("Request was aborted".to_string()));
+        }
+    }
+}
+
+pub mod pool {
+    use crate::errors::AppError;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+
+    #[derive(Default)]
+    struct Queue {
+        /// Tickets of waiting requests, oldest first
+        waiting: VecDeque<u64>,
+        next_ticket: u64,
+    }
+
+    /// Concurrency limit with a bounded FIFO wait queue
+    #[derive(Clone)]
+    pub struct WorkerPool {
+        name: &'static str,
+        workers: usize,
+        max_queue: usize,
+        retry_after_secs: u64,
+        permits: Arc<Semaphore>,
+        queue: Arc<Mutex<Queue>>,
+        /// Bumped whenever the queue shrinks so waiters can report their new position
+        moved: watch::Sender<()>,
+    }
+
+    impl WorkerPool {
+        pub fn new(name: &'static str, workers: usize, max_queue: usize, retry_after_secs: u64) -> Self {
+            let (moved, _) = watch::channel(());
+            Self {
+                name,
+                workers,
+                max_queue,
+                retry_after_secs,
+                permits: Arc::new(Semaphore::new(workers)),
+                queue: Arc::new(Mutex::new(Queue::default())),
+                moved,
+            }
+        }
+
+        pub fn workers(&self) -> usize {
+            self.workers
+        }
+
+        /// Workers currently running a conversion
+        pub fn busy(&self) -> usize {
+            self.workers - self.permits.available_permits()
+        }
+
+        /// Requests waiting for a worker
+        pub fn queued(&self) -> usize {
+            self.queue.lock().unwrap_or_else(|e| e.into_inner()).waiting.len()
+        }
+
+        /// Wait for a free worker, calling `on_position` with the 1-based queue
+        /// position whenever it changes
+        ///
+        /// Fails with `ServerBusy` right away when the queue is full. Dropping
+        /// the future leaves the queue.
+        pub async fn acquire(&self, mut on_position: impl FnMut(usize)) -> Result<OwnedSemaphorePermit, AppError> {
+            if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+                return Ok(permit);
+            }
+
+            let ticket = {
+                let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+                if queue.waiting.len() >= self.max_queue {
+                    tracing::warn!("🚦 {} queue is full ({} waiting)", self.name, queue.waiting.len());
+                    return Err(AppError::ServerBusy {
+                        message: format!("All {} workers are busy and the queue is full, try again later", self.name),
+                        retry_after_secs: self.retry_after_secs,
+                    });
+                }
+                let ticket = queue.next_ticket;
+                queue.next_ticket += 1;
+                queue.waiting.push_back(ticket);
+                ticket
+            };
+            let _entry = QueueEntry { pool: self, ticket };
+
+            let mut moved = self.moved.subscribe();
+            let acquire = self.permits.clone().acquire_owned();
+            tokio::pin!(acquire);
+            let mut last_position = None;
+            loop {
+                let position = self.position(ticket);
+                if last_position != Some(position) {
+                    tracing::debug!("🚦 Waiting for a {} worker at position {}", self.name, position);
+                    on_position(position);
+                    last_position = Some(position);
+                }
+                tokio::select! {
+                    permit = &mut acquire => {
+                        return permit.map_err(|_| AppError::InternalError(format!("{} worker pool was closed", self.name)));
+                    }
+                    _ = moved.changed() => {}
+                }
+            }
+        }
+
+        fn position(&self, ticket: u64) -> usize {
+            let queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+            queue.waiting.iter().position(|t| *t == ticket).map_or(1, |index| index + 1)
+        }
+    }
+
+    /// Removes a ticket from the queue once its request got a worker or went away
+    struct QueueEntry<'a> {
+        pool: &'a WorkerPool,
+        ticket: u64,
+    }
+
+    impl Drop for QueueEntry<'_> {
+        fn drop(&mut self) {
+            let mut queue = self.pool.queue.lock().unwrap_or_else(|e| e.into_inner());
+            queue.waiting.retain(|t| *t != self.ticket);
+            drop(queue);
+            self.pool.moved.send_replace(());
+        }
+    }
+}
+
+pub mod upload {
+    use crate::errors::{AppError, ToolFailure};
+    use axum::extract::multipart::{Field, MultipartError};
+    use axum::http::StatusCode;
+    use sha2::{Digest, Sha256};
+    use std::path::Path;
+    use tempfile::NamedTempFile;
+    use tokio::io::AsyncWriteExt;
+
+    /// Largest video or audio file accepted by the ffmpeg endpoints
+    pub const MAX_MEDIA_BYTES: u64 = 200 * 1024 * 1024;
+    /// Leading bytes kept in memory for container sniffing
+    const HEAD_BYTES: usize = 64;
+
+    /// An uploaded file streamed to a temp file, deleted when dropped
+    pub struct Upload {
+        file: NamedTempFile,
+        pub filename: Option<String>,
+        pub size: u64,
+        /// Hex SHA-256 of the file contents
+        pub sha256: String,
+        head: Vec<u8>,
+    }
+
+    impl Upload {
+        pub fn path(&self) -> &Path {
+            self.file.path()
+        }
+
+        /// First bytes of the file, enough to recognize the container
+        pub fn head(&self) -> &[u8] {
+            &self.head
+        }
+    }
+
+    /// Stream a multipart file field to disk, hashing it and enforcing `max_bytes` as it arrives
+    pub async fn receive(mut field: Field<'_>, name: &str, max_bytes: u64) -> Result<Upload, AppError> {
+        let filename = field.file_name().map(|s| s.to_string());
+        let file = NamedTempFile::new().map_err(|e| AppError::InternalError(format!("Failed to create temp input file: {}", e)))?;
+        let mut writer = file
+            .as_file()
+            .try_clone()
+            .map(tokio::fs::File::from_std)
+            .map_err(|e| AppError::InternalError(format!("Failed to open temp input file: {}", e)))?;
+
+        let mut hasher = Sha256::new();
+        let mut head = Vec::with_capacity(HEAD_BYTES);
+        let mut size: u64 = 0;
+        while let Some(chunk) = field.chunk().await.map_err(|e| read_error(name, e))? {
+            size += chunk.len() as u64;
+            if size > max_bytes {
+                return Err(AppError::PayloadTooLarge(format!(
+                    "{} file exceeds the {} MB limit",
+                    name,
+                    max_bytes / (1024 * 1024)
+                )));
+            }
+            if head.len() < HEAD_BYTES {
+                let take = (HEAD_BYTES - head.len()).min(chunk.len());
+                head.extend_from_slice(&chunk[..take]);
+            }
+            hasher.update(&chunk);
+            writer.write_all(&chunk).await.map_err(write_error)?;
+        }
+        writer.flush().await.map_err(write_error)?;
+
+        let sha256 = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        tracing::info!("📁 Received file: {:?}, size: {} bytes, sha256: {}", filename, size, sha256);
+        Ok(Upload { file, filename, size, sha256, head })
+    }
+
+    fn read_error(name: &str, error: MultipartError) -> AppError {
+        tracing::error!("Failed to read {} data: {}", name, error);
+        if error.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            AppError::PayloadTooLarge(format!("Request body exceeds the {} MB limit", MAX_MEDIA_BYTES / (1024 * 1024)))
+        } else {
+            AppError::BadRequest(format!("Failed to read {} data", name))
+        }
+    }
+
+    fn write_error(error: std::io::Error) -> AppError {
+        if error.kind() == std::io::ErrorKind::StorageFull {
+            return AppError::InsufficientStorage(ToolFailure {
+                message: "The server ran out of disk space while receiving the upload".to_string(),
+                diagnostic: None,
+            });
+        }
+        AppError::InternalError(format!("Failed to write uploaded file: {}", error))
     }
 }
@@ -0,0 +1,28 @@
+use crate::config::WorkerConfig;
+use crate::services::jobs::JobRegistry;
+use crate::services::pool::WorkerPool;
+
+/// Seconds clients are asked to wait before retrying when a queue is full
+const IMAGE_RETRY_AFTER_SECS: u64 = 2;
+const VIDEO_RETRY_AFTER_SECS: u64 = 30;
+
+/// Shared application state available to all handlers
+#[derive(Clone)]
+pub struct AppState {
+    /// Progress channels of running conversion jobs
+    pub jobs: JobRegistry,
+    /// Limits concurrent PNG to WebP conversions
+    pub image_pool: WorkerPool,
+    /// Limits concurrent ffmpeg jobs
+    pub video_pool: WorkerPool,
+}
+
+impl AppState {
+    pub fn new(workers: &WorkerConfig) -> Self {
+        Self {
+            jobs: JobRegistry::default(),
+            image_pool: WorkerPool::new("image", workers.image_workers, workers.image_queue, IMAGE_RETRY_AFTER_SECS),
+            video_pool: WorkerPool::new("video", workers.video_workers, workers.video_queue, VIDEO_RETRY_AFTER_SECS),
+        }
+    }
+}
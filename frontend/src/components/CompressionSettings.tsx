@@ -3,6 +3,8 @@
 import { useState } from 'react'
 import { Settings, Info, ChevronDown, ChevronUp } from 'lucide-react'
 import { CompressionQuality, VideoCompressionSettings } from '@/lib/api'
+import { SizeEstimate } from '@/lib/types'
+import { formatBytes } from '@/hooks/use-file-upload'
 import { Card, CardContent, CardHeader, CardTitle } from '@/components/ui/card'
 import { Button } from '@/components/ui/button'
 import { Badge } from '@/components/ui/badge'
@@ -18,6 +20,7 @@ interface CompressionSettingsProps {
   readonly settings: VideoCompressionSettings
   readonly onSettingsChange: (settings: VideoCompressionSettings) => void
   readonly disabled?: boolean
+  readonly estimates?: SizeEstimate[]  // Estimated from the /api/inspect-video probe of the selected file
 }
 
 const qualityOptions: Array<{
@@ -75,10 +78,21 @@ const audioBitrateOptions = [
 export function CompressionSettings({ 
   settings, 
   onSettingsChange, 
-  disabled = false 
+  disabled = false,
+  estimates
 }: CompressionSettingsProps) {
   const [isOpen, setIsOpen] = useState(false)
 
+  // Prefer the estimate for the selected file over the generic range
+  const estimatedSize = (quality: CompressionQuality, fallback: string) => {
+    const estimate = estimates?.find(e => e.quality === quality)
+    if (!estimate) return fallback
+    const reduction = Math.round(estimate.reduction_percent)
+    return reduction >= 0
+      ? `~${formatBytes(estimate.estimated_size)} (${reduction}% reduction)`
+      : `~${formatBytes(estimate.estimated_size)} (${-reduction}% larger)`
+  }
+
   const currentQuality = qualityOptions.find(opt => opt.value === settings.quality)
   const currentAudioBitrate = audioBitrateOptions.find(opt => opt.value === (settings.audioBitrate || '64k'))
 
@@ -154,7 +168,7 @@ export function CompressionSettings({
                           {option.description}
                         </div>
                         <div className="text-xs text-muted-foreground">
-                          {option.estimatedSize} • {option.speed} encoding
+                          {estimatedSize(option.value, option.estimatedSize)} • {option.speed} encoding
                         </div>
                       </DropdownMenuItem>
                     ))}
@@ -164,7 +178,7 @@ export function CompressionSettings({
 
               {currentQuality && (
                 <div className="text-xs text-muted-foreground p-3 bg-muted rounded-md space-y-1">
-                  <div><strong>Expected reduction:</strong> {currentQuality.estimatedSize}</div>
+                  <div><strong>Expected size:</strong> {estimatedSize(currentQuality.value, currentQuality.estimatedSize)}</div>
                   <div><strong>Encoding speed:</strong> {currentQuality.speed}</div>
                   <div><strong>Description:</strong> {currentQuality.description}</div>
                 </div>
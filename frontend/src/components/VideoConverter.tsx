@@ -1,6 +1,6 @@
  'use client'
 
-import { useState, useCallback } from 'react'
+import { useState, useCallback, useEffect, useRef } from 'react'
 import {
   Upload,
   Download,
@@ -15,9 +15,10 @@ import {
 } from 'lucide-react'
 import {
   convertVideo,
+  inspectVideo,
   type VideoCompressionSettings
 } from '@/lib/api'
-import { ConvertResponse } from '@/lib/types'
+import { ConvertResponse, InspectResponse } from '@/lib/types'
 import {
   useFileUpload,
   formatBytes,
@@ -88,7 +89,7 @@ export function VideoConverter() {
   ] = useFileUpload({
     maxFiles: 20,
     maxSize: 200 * 1024 * 1024, // 200MB
-    accept: 'video/mp4',
+    accept: 'video/mp4,video/quicktime,video/x-matroska,video/webm,video/x-msvideo,image/gif,.mp4,.mov,.mkv,.webm,.avi,.gif',
     multiple: true,
     onFilesChange: (newFiles) => {
       const newUploadFiles = newFiles.map((file): FileUploadItem => {
@@ -106,8 +107,53 @@ export function VideoConverter() {
     },
   })
 
-  const convertSingleFile = useCallback(async (file: File): Promise<ConvertResponse> => {
-    return await convertVideo(file, compressionSettings)
+  // Probe of the first file waiting to be converted; each file is uploaded once per audio bitrate
+  const probeCache = useRef(new WeakMap<File, Map<string, Promise<InspectResponse>>>())
+  const [probe, setProbe] = useState<InspectResponse | undefined>()
+  const estimateFile = uploadFiles.find(file => file.status === 'pending')?.file
+  const audioBitrate = compressionSettings.audioBitrate ?? ''
+
+  useEffect(() => {
+    setProbe(undefined)
+    if (!estimateFile) {
+      return
+    }
+
+    const requests = probeCache.current.get(estimateFile) ?? new Map<string, Promise<InspectResponse>>()
+    probeCache.current.set(estimateFile, requests)
+    let request = requests.get(audioBitrate)
+    if (!request) {
+      const pending = inspectVideo(estimateFile, audioBitrate)
+      requests.set(audioBitrate, pending)
+      // Let a failed inspection be retried the next time the file is selected
+      pending.catch(() => requests.delete(audioBitrate))
+      request = pending
+    }
+
+    let cancelled = false
+    request
+      .then(result => {
+        if (!cancelled) setProbe(result)
+      })
+      .catch(() => {
+        if (!cancelled) setProbe(undefined)
+      })
+    return () => {
+      cancelled = true
+    }
+  }, [estimateFile, audioBitrate])
+
+  const estimates = probe?.estimates
+
+  const convertSingleFile = useCallback(async (
+    file: File,
+    onProgress?: (percent: number) => void
+  ): Promise<ConvertResponse> => {
+    return await convertVideo(file, compressionSettings, (progress) => {
+      if (progress.percent !== null) {
+        onProgress?.(progress.percent)
+      }
+    })
   }, [compressionSettings])
 
   const convertAllFiles = useCallback(async () => {
@@ -129,11 +175,15 @@ export function VideoConverter() {
         try {
           setUploadFiles(prev => prev.map(f => 
             f.id === fileItem.id 
-              ? { ...f, status: 'uploading' as const, progress: 50 }
+              ? { ...f, status: 'uploading' as const, progress: 0 }
               : f
           ))
 
-          const result = await convertSingleFile(fileItem.file)
+          const result = await convertSingleFile(fileItem.file, (percent) => {
+            setUploadFiles(prev => prev.map(f =>
+              f.id === fileItem.id ? { ...f, progress: percent } : f
+            ))
+          })
 
           setUploadFiles(prev => prev.map(f => 
             f.id === fileItem.id 
@@ -248,6 +298,7 @@ export function VideoConverter() {
             settings={compressionSettings}
             onSettingsChange={setCompressionSettings}
             disabled={isConverting}
+            estimates={estimates}
           />
 
           <Separator className="my-4" />
@@ -295,7 +346,7 @@ export function VideoConverter() {
                 <p className="text-sm font-medium">
                   {isConverting 
                     ? 'Converting files...' 
-                    : 'Drop MP4 files here or click to browse'
+                    : 'Drop MP4, MOV, MKV, WebM, AVI or GIF files here or click to browse'
                   }
                 </p>
                 <p className="text-xs text-muted-foreground">
@@ -47,6 +47,9 @@ export function useConversionHistory() {
   }, [history]);
 
   const addToHistory = useCallback((result: ConvertResponse) => {
+    // The backend returned the original file unchanged, nothing was saved
+    if (result.converted === false) return;
+
     const historyItem: ConversionHistoryItem = {
       id: `${Date.now()}-${Math.random().toString(36).substr(2, 9)}`,
       filename: result.filename,
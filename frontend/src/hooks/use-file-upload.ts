@@ -72,7 +72,12 @@ export function useFileUpload(options: FileUploadOptions) {
       }
 
       // Check file type
-      if (accept !== '*' && !accept.split(',').some(type => file.type.includes(type.trim()))) {
+      const isAccepted = accept.split(',').some(entry => {
+        const type = entry.trim().toLowerCase();
+        // Extension entries (".mkv") match the file name, others the MIME type
+        return type.startsWith('.') ? file.name.toLowerCase().endsWith(type) : file.type.includes(type);
+      });
+      if (accept !== '*' && !isAccepted) {
         newErrors.push(`File "${file.name}" is not an accepted file type`);
         continue;
       }
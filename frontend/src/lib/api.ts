@@ -1,5 +1,5 @@
 import axios from 'axios'
-import { ConvertResponse, ApiError, BatchConvertResponse } from './types'
+import { ConvertResponse, ApiError, BatchConvertResponse, InspectResponse, JobProgress } from './types'
 
 const API_BASE_URL = process.env.NEXT_PUBLIC_API_URL || 'http://localhost:8080'
 
@@ -71,17 +71,36 @@ export async function convertImage(file: File): Promise<ConvertResponse> {
   }
 }
 
+/**
+ * Video containers accepted by the backend
+ */
+const SUPPORTED_VIDEO_EXTENSIONS = ['mp4', 'm4v', 'mov', 'mkv', 'webm', 'avi', 'gif']
+
 /**
  * Video compression quality levels
  */
 export type CompressionQuality = 'maximum' | 'high' | 'balanced' | 'low' | 'minimal'
 
+/**
+ * Output video codecs
+ */
+export type VideoCodec = 'vp9' | 'av1' | 'vp8'
+
 /**
  * Video compression settings
  */
 export interface VideoCompressionSettings {
   quality: CompressionQuality
+  videoCodec?: VideoCodec
   audioBitrate?: string
+  videoBitrate?: string
+  maxSizeMb?: number
+  targetVmaf?: number  // Search for the CRF reaching this mean VMAF (50-99)
+  maxWidth?: number
+  maxHeight?: number
+  fps?: number
+  outputFormat?: 'webm' | 'webp' | 'gif'
+  qualityMetrics?: boolean  // Score the WebM against the source (VMAF, SSIM, PSNR)
 }
 
 /**
@@ -115,31 +134,97 @@ function createVideoFormData(file: File, settings?: VideoCompressionSettings): F
     console.log('📋 Adding compression settings:', settings)
     formData.append('quality', settings.quality)
     console.log('  ✓ Added quality:', settings.quality)
+    if (settings.videoCodec) {
+      formData.append('video_codec', settings.videoCodec)
+      console.log('  ✓ Added video_codec:', settings.videoCodec)
+    }
     if (settings.audioBitrate) {
       formData.append('audio_bitrate', settings.audioBitrate)
       console.log('  ✓ Added audio_bitrate:', settings.audioBitrate)
     }
+    if (settings.videoBitrate) {
+      formData.append('video_bitrate', settings.videoBitrate)
+      console.log('  ✓ Added video_bitrate:', settings.videoBitrate)
+    }
+    if (settings.maxSizeMb !== undefined) {
+      formData.append('max_size_mb', settings.maxSizeMb.toString())
+      console.log('  ✓ Added max_size_mb:', settings.maxSizeMb)
+    }
+    if (settings.targetVmaf !== undefined) {
+      formData.append('target_vmaf', settings.targetVmaf.toString())
+      console.log('  ✓ Added target_vmaf:', settings.targetVmaf)
+    }
+    if (settings.maxWidth !== undefined) {
+      formData.append('max_width', settings.maxWidth.toString())
+      console.log('  ✓ Added max_width:', settings.maxWidth)
+    }
+    if (settings.maxHeight !== undefined) {
+      formData.append('max_height', settings.maxHeight.toString())
+      console.log('  ✓ Added max_height:', settings.maxHeight)
+    }
+    if (settings.fps !== undefined) {
+      formData.append('fps', settings.fps.toString())
+      console.log('  ✓ Added fps:', settings.fps)
+    }
+    if (settings.outputFormat) {
+      formData.append('output_format', settings.outputFormat)
+      console.log('  ✓ Added output_format:', settings.outputFormat)
+    }
+    if (settings.qualityMetrics) {
+      formData.append('quality_metrics', 'true')
+      console.log('  ✓ Added quality_metrics')
+    }
   }
   
   return formData
 }
 
+/**
+ * Subscribe to Server-Sent Events progress updates of a conversion job
+ * @returns Function that closes the subscription
+ */
+function subscribeToJobProgress(jobId: string, onProgress: (progress: JobProgress) => void): () => void {
+  const source = new EventSource(`${API_BASE_URL}/api/jobs/${encodeURIComponent(jobId)}/events`)
+
+  source.addEventListener('progress', (event) => {
+    const progress = JSON.parse((event as MessageEvent).data) as JobProgress
+    onProgress(progress)
+    if (progress.status === 'completed' || progress.status === 'failed' || progress.status === 'cancelled') {
+      source.close()
+    }
+  })
+  source.onerror = () => source.close()
+
+  return () => source.close()
+}
+
 /**
  * Convert MP4 video to WebM format
  * @param file - MP4 file to convert
  * @param settings - Optional compression settings
+ * @param onProgress - Optional callback receiving live ffmpeg progress
  * @returns Promise<ConvertResponse> - Conversion result with WebM data (base64)
  */
 export async function convertVideo(
   file: File, 
-  settings?: VideoCompressionSettings
+  settings?: VideoCompressionSettings,
+  onProgress?: (progress: JobProgress) => void
 ): Promise<ConvertResponse> {
-  if (!file.type.includes('mp4') && !file.name.toLowerCase().endsWith('.mp4')) {
-    throw new Error('Only MP4 files are supported')
+  const extension = file.name.toLowerCase().split('.').pop() ?? ''
+  if (!SUPPORTED_VIDEO_EXTENSIONS.includes(extension)) {
+    throw new Error('Only MP4, MOV, MKV, WebM, AVI and GIF files are supported')
   }
 
   const formData = createVideoFormData(file, settings)
 
+  // Subscribe before uploading so no progress update is missed
+  let unsubscribe: (() => void) | undefined
+  if (onProgress) {
+    const jobId = crypto.randomUUID()
+    formData.append('job_id', jobId)
+    unsubscribe = subscribeToJobProgress(jobId, onProgress)
+  }
+
   try {
     const response = await api.post<ConvertResponse>('/api/convert-video', formData, {
       headers: {
@@ -148,6 +233,46 @@ export async function convertVideo(
       timeout: 300000, // 5 minutes timeout for video conversion
     })
 
+    return response.data
+  } catch (error) {
+    handleVideoConversionError(error)
+  } finally {
+    unsubscribe?.()
+  }
+}
+
+/**
+ * Cancel a running conversion job, stopping its ffmpeg process
+ * @param jobId - Job id passed to the conversion request
+ */
+export async function cancelJob(jobId: string): Promise<void> {
+  try {
+    await api.post(`/api/jobs/${encodeURIComponent(jobId)}/cancel`)
+  } catch (error) {
+    handleVideoConversionError(error)
+  }
+}
+
+/**
+ * Inspect a video with ffprobe and get estimated output sizes
+ * @param file - Video file to inspect
+ * @param audioBitrate - Audio bitrate assumed for the size estimates
+ * @returns Promise<InspectResponse> - Media details and per-quality size estimates
+ */
+export async function inspectVideo(file: File, audioBitrate?: string): Promise<InspectResponse> {
+  const formData = new FormData()
+  formData.append('video', file)
+  if (audioBitrate) {
+    formData.append('audio_bitrate', audioBitrate)
+  }
+
+  try {
+    const response = await api.post<InspectResponse>('/api/inspect-video', formData, {
+      headers: {
+        'Content-Type': 'multipart/form-data',
+      },
+    })
+
     return response.data
   } catch (error) {
     handleVideoConversionError(error)
@@ -198,7 +323,8 @@ export async function convertMultipleImages(
 export async function checkHealth(): Promise<boolean> {
   try {
     const response = await api.get('/health')
-    return response.data.status === 'healthy'
+    // 'degraded' means ffmpeg is missing; image conversion still works
+    return response.data.status === 'healthy' || response.data.status === 'degraded'
   } catch {
     return false
   }
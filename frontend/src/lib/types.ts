@@ -6,6 +6,91 @@ export interface ConvertResponse {
   original_size: number
   converted_size: number
   compression_ratio: number  // Percentage saved
+  converted: boolean  // False when the original file was returned unchanged
+  encoding_mode?: 'lossy' | 'lossless'  // WebP mode actually used
+  ssim?: number  // Structural similarity (auto mode only)
+  source_profile?: string  // Embedded ICC profile name of the input
+  job_id?: string  // Job id used for progress and queue position reporting
+  video_encoder?: string  // ffmpeg encoder used (video only)
+  video_bitrate_kbps?: number  // Target video bitrate of a bitrate or size-limited encode
+  poster_data?: string  // Base64 encoded WebP poster frame (video only, when requested)
+  input_sha256?: string  // Hex SHA-256 of the uploaded file (video and audio only)
+  quality_metrics?: QualityMetrics  // Output compared with the source (video only, when requested)
+  crf_search?: CrfSearch  // CRF chosen for target_vmaf (video only)
+}
+
+export interface ScoreSummary {
+  mean: number
+  min: number
+  p1: number  // 1st percentile
+  p5: number  // 5th percentile
+  median: number
+}
+
+export interface QualityMetrics {
+  frames: number
+  vmaf?: ScoreSummary  // 0-100, absent when ffmpeg lacks libvmaf
+  ssim: ScoreSummary | null  // 0-1
+  psnr: ScoreSummary | null  // dB, capped at 100
+}
+
+export interface CrfSample {
+  crf: number
+  vmaf: number  // Mean VMAF of the samples encoded at this CRF
+}
+
+export interface CrfSearch {
+  target_vmaf: number
+  crf: number  // CRF used for the full encode
+  predicted_vmaf: number
+  samples: CrfSample[]
+}
+
+export interface JobProgress {
+  job_id: string
+  status: 'pending' | 'queued' | 'running' | 'completed' | 'failed' | 'cancelled'
+  percent: number | null
+  out_time_secs: number
+  duration_secs: number | null
+  fps: number | null
+  speed: number | null
+  bitrate_kbps: number | null
+  eta_secs: number | null
+  queue_position: number | null  // 1-based place in the worker queue while queued
+  message: string | null
+}
+
+export interface StreamInfo {
+  index: number | null
+  codec_type: string | null  // "video", "audio", "subtitle", ...
+  codec_name: string | null
+  width?: number
+  height?: number
+  frame_rate?: number
+  rotation?: number  // Degrees clockwise
+  bitrate_kbps?: number
+  channels?: number
+  sample_rate?: number
+}
+
+export interface SizeEstimate {
+  quality: 'maximum' | 'high' | 'balanced' | 'low' | 'minimal'
+  estimated_size: number  // Bytes
+  reduction_percent: number
+}
+
+export interface InspectResponse {
+  success: boolean
+  filename: string | null
+  size: number
+  sha256: string  // Hex SHA-256 of the uploaded file
+  container: string
+  format_name: string | null
+  duration_secs: number | null
+  bitrate_kbps: number | null
+  streams: StreamInfo[]
+  estimates: SizeEstimate[]  // Empty when duration or resolution is unknown
+  job_id: string  // Job id used for queue position reporting
 }
 
 export interface BatchConvertResponse {
@@ -22,5 +107,9 @@ export interface ApiError {
   success: boolean
   error: string
   code: string
+  field?: string  // Rejected form field (INVALID_PARAMETER only)
+  allowed?: { one_of: string[] } | { range: { min: string; max: string } } | { format: string }
+  diagnostic?: string  // Sanitized tail of the ffmpeg log when ffmpeg failed
+  retry_after_secs?: number  // SERVER_BUSY only
   timestamp: string
 }